@@ -0,0 +1,52 @@
+//! Derive macros for `tea_fixed_arena`
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error};
+
+/// Implement `tea_fixed_arena::zeroable::Zeroable` for a struct whose fields
+/// are all `Zeroable`. Type parameters get a `Zeroable` bound, and a struct
+/// with a field that can't be all zeros fails to compile.
+/// Enums and unions are not supported.
+#[proc_macro_derive(Zeroable)]
+pub fn derive_zeroable(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(_) | Data::Union(_) => {
+            return Error::new_spanned(
+                &input.ident,
+                "Zeroable can only be derived for structs",
+            )
+            .to_compile_error()
+            .into();
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param
+            .bounds
+            .push(parse_quote!(::tea_fixed_arena::zeroable::Zeroable));
+    }
+
+    let name = &input.ident;
+    let field_types = fields.iter().map(|field| &field.ty);
+    let (impl_generics, type_generics, where_clause) =
+        input.generics.split_for_impl();
+    let result = quote! {
+        unsafe impl #impl_generics ::tea_fixed_arena::zeroable::Zeroable
+            for #name #type_generics #where_clause {}
+
+        const _: () = {
+            #[allow(dead_code)]
+            fn assert_fields_zeroable #impl_generics () #where_clause {
+                fn assert_zeroable<
+                    T: ::tea_fixed_arena::zeroable::Zeroable,
+                >() {}
+                #(assert_zeroable::<#field_types>();)*
+            }
+        };
+    };
+    result.into()
+}
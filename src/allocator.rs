@@ -0,0 +1,256 @@
+use core::{
+    alloc::{AllocError as CoreAllocError, Allocator, Layout},
+    ptr::{self, NonNull},
+};
+
+#[cfg(feature = "track-callers")]
+use crate::callers::Caller;
+use crate::FixedArena;
+
+/// Lets standard containers allocate from a fixed arena, for example with
+/// `Vec::new_in(&arena)` or `Box::new_in(value, &arena)`.
+/// Memory is only given back to the arena when the most recent allocation is
+/// deallocated or shrunk. Growing the most recent allocation happens in place.
+/// With the `track-callers` feature, memory allocated this way is totaled in
+/// `CallerReport::allocator_bytes` instead of under a call site, since
+/// containers don't pass on their caller.
+/// # Examples
+/// ```
+/// # #![feature(allocator_api)]
+/// # use tea_fixed_arena::FixedArena;
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let mut values = Vec::new_in(&arena);
+/// for index in 0..16 {
+///     values.push(index);
+/// }
+/// let boxed = Box::new_in(5, &arena);
+/// assert_eq!(values.len(), 16);
+/// assert_eq!(*boxed, 5);
+/// ```
+unsafe impl Allocator for &FixedArena<'_> {
+    fn allocate(
+        &self,
+        layout: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        #[cfg(feature = "track-callers")]
+        let _caller = self.record_for(Caller::Allocator);
+        let pointer = self
+            .get_alloc_ptr_with_layout::<[u8]>(layout)
+            .map_err(|_| CoreAllocError)?;
+        let pointer = unsafe { NonNull::new_unchecked(pointer) };
+        Ok(NonNull::slice_from_raw_parts(pointer, layout.size()))
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        #[cfg(feature = "track-callers")]
+        let _caller = self.record_for(Caller::Allocator);
+        let _ =
+            self.resize_last(ptr.as_ptr(), layout.size(), 0, layout.align());
+    }
+
+    unsafe fn grow(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        #[cfg(feature = "track-callers")]
+        let _caller = self.record_for(Caller::Allocator);
+        if ptr.as_ptr().align_offset(new_layout.align()) == 0
+            && self
+                .resize_last(
+                    ptr.as_ptr(),
+                    old_layout.size(),
+                    new_layout.size(),
+                    new_layout.align(),
+                )
+                .is_ok()
+        {
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            old_layout.size(),
+        );
+        Ok(new_ptr)
+    }
+
+    unsafe fn grow_zeroed(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        let new_ptr = self.grow(ptr, old_layout, new_layout)?;
+        let tail = (new_ptr.as_ptr() as *mut u8).add(old_layout.size());
+        ptr::write_bytes(tail, 0, new_layout.size() - old_layout.size());
+        Ok(new_ptr)
+    }
+
+    unsafe fn shrink(
+        &self,
+        ptr: NonNull<u8>,
+        old_layout: Layout,
+        new_layout: Layout,
+    ) -> Result<NonNull<[u8]>, CoreAllocError> {
+        #[cfg(feature = "track-callers")]
+        let _caller = self.record_for(Caller::Allocator);
+        if ptr.as_ptr().align_offset(new_layout.align()) == 0 {
+            let _ = self.resize_last(
+                ptr.as_ptr(),
+                old_layout.size(),
+                new_layout.size(),
+                new_layout.align(),
+            );
+            return Ok(NonNull::slice_from_raw_parts(ptr, new_layout.size()));
+        }
+
+        let new_ptr = self.allocate(new_layout)?;
+        ptr::copy_nonoverlapping(
+            ptr.as_ptr(),
+            new_ptr.as_ptr() as *mut u8,
+            new_layout.size(),
+        );
+        Ok(new_ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{boxed::Box, collections::BTreeMap, vec::Vec};
+
+    use crate::test_common::I32Struct;
+
+    const DEFAULT_ALIGN: usize = 4;
+
+    /// Test pushing into a vector backed by the arena
+    #[test]
+    fn vec_push() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values = Vec::new_in(&arena);
+        for index in 0..64 {
+            values.push(I32Struct {
+                x: index,
+                y: -index,
+            });
+        }
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!(value.x, index as i32);
+            assert_eq!(value.y, -(index as i32));
+        }
+    }
+
+    /// Test that growing the most recent allocation happens in place
+    #[test]
+    fn vec_grows_in_place() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values: Vec<u32, _> = Vec::with_capacity_in(4, &arena);
+        values.extend(0..4);
+        let start = values.as_ptr();
+
+        values.reserve_exact(60);
+        assert_eq!(values.as_ptr(), start);
+        assert_eq!(arena.used.get(), 64 * 4);
+    }
+
+    /// Test that growing an allocation that isn't the most recent moves it
+    #[test]
+    fn vec_grows_by_moving() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values: Vec<u32, _> = Vec::with_capacity_in(4, &arena);
+        values.extend(0..4);
+        let start = values.as_ptr();
+        arena.alloc(1_u32).unwrap();
+
+        values.reserve_exact(4);
+        assert_ne!(values.as_ptr(), start);
+        assert_eq!(values, [0, 1, 2, 3]);
+        assert_eq!(arena.used.get(), 4 * 4 + 4 + 8 * 4);
+    }
+
+    /// Test that deallocating the most recent allocation gives the bytes back
+    #[test]
+    fn deallocate_last() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc(1_u32).unwrap();
+        let boxed = Box::new_in(I32Struct { x: 1, y: 2 }, &arena);
+        assert_eq!(boxed.y, 2);
+        assert_eq!(arena.used.get(), 12);
+
+        drop(boxed);
+        assert_eq!(arena.used.get(), 4);
+    }
+
+    /// Test that deallocating an older allocation leaves the arena as is
+    #[test]
+    fn deallocate_not_last() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let first = Box::new_in(1_u32, &arena);
+        let second = Box::new_in(2_u32, &arena);
+
+        drop(first);
+        assert_eq!(arena.used.get(), 8);
+        drop(second);
+        assert_eq!(arena.used.get(), 4);
+    }
+
+    /// Test that shrinking the most recent allocation gives the bytes back
+    #[test]
+    fn shrink_last() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values: Vec<u32, _> = Vec::with_capacity_in(16, &arena);
+        values.extend(0..4);
+        values.shrink_to_fit();
+        assert_eq!(values, [0, 1, 2, 3]);
+        assert_eq!(arena.used.get(), 4 * 4);
+    }
+
+    /// Test that running out of space is reported to the container
+    #[test]
+    fn at_capacity() {
+        let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+        let mut values: Vec<u32, _> = Vec::new_in(&arena);
+        assert!(values.try_reserve_exact(16).is_ok());
+        assert!(values.try_reserve_exact(17).is_err());
+    }
+
+    /// Test that allocations by containers are totaled apart from the call
+    /// sites
+    #[cfg(feature = "track-callers")]
+    #[test]
+    fn report() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values: Vec<u32, _> = Vec::with_capacity_in(4, &arena);
+        values.extend(0..4);
+        values.reserve_exact(4);
+        let line = line!() + 1;
+        arena.alloc(1_u32).unwrap();
+
+        let report = arena.report();
+        assert_eq!(report.allocator_bytes, 8 * 4);
+        assert_eq!(report.allocator_allocations, 1);
+        let lines: Vec<_> =
+            report.iter().map(|site| site.location.line()).collect();
+        assert_eq!(lines, [line]);
+        assert!(report
+            .to_string()
+            .contains("via Allocator: 32 bytes in 1 allocations"));
+    }
+
+    /// Test a map that makes many allocations of different sizes
+    #[test]
+    fn btree_map() {
+        let arena = FixedArena::with_capacity(64 * 1024, DEFAULT_ALIGN);
+        let mut map = BTreeMap::new_in(&arena);
+        for index in 0..256_u32 {
+            map.insert(index, index * 2);
+        }
+        for index in 0..256_u32 {
+            assert_eq!(map[&index], index * 2);
+        }
+    }
+}
@@ -0,0 +1,38 @@
+//! Calls into the AddressSanitizer manual poisoning interface when the crate
+//! is built with `-Zsanitizer=address`, and does nothing otherwise.
+//! Memory in an arena that is not part of an allocation is kept poisoned, so
+//! ASan reports accesses that overflow an allocation into its neighbor or
+//! that use memory after it was released. ASan tracks memory in 8 byte
+//! granules and can only poison the end of a granule, so padding that shares
+//! a granule with a later allocation stays accessible.
+
+#[cfg(sanitize = "address")]
+unsafe extern "C" {
+    fn __asan_poison_memory_region(addr: *const core::ffi::c_void, size: usize);
+    fn __asan_unpoison_memory_region(
+        addr: *const core::ffi::c_void,
+        size: usize,
+    );
+}
+
+/// Mark the `size` bytes at `pointer` as off limits
+#[inline]
+pub(crate) fn poison(pointer: *const u8, size: usize) {
+    #[cfg(sanitize = "address")]
+    unsafe {
+        __asan_poison_memory_region(pointer.cast(), size)
+    };
+    #[cfg(not(sanitize = "address"))]
+    let _ = (pointer, size);
+}
+
+/// Mark the `size` bytes at `pointer` as usable
+#[inline]
+pub(crate) fn unpoison(pointer: *const u8, size: usize) {
+    #[cfg(sanitize = "address")]
+    unsafe {
+        __asan_unpoison_memory_region(pointer.cast(), size)
+    };
+    #[cfg(not(sanitize = "address"))]
+    let _ = (pointer, size);
+}
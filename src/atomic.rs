@@ -0,0 +1,491 @@
+use core::{alloc::Layout, marker::PhantomData, mem::MaybeUninit, ptr, slice};
+
+#[cfg(loom)]
+use loom::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(not(loom))]
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(any(feature = "std", test))]
+use std::alloc::dealloc;
+
+#[cfg(any(feature = "std", test))]
+use crate::alloc_buffer;
+use crate::{
+    errors::{AllocError, TopCallers},
+    zeroable::Zeroable,
+    FixedArena,
+};
+
+/// A fixed arena that can be shared between threads. The amount of memory used
+/// is bumped with a compare-exchange loop instead of a lock, and capacity is
+/// enforced exactly: an allocation either fits entirely or fails.
+/// `reset` mutably borrows the arena, so no thread can still be using an
+/// allocation when the memory is reused.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::atomic::AtomicFixedArena;
+/// let arena = AtomicFixedArena::with_capacity(4096, 8);
+/// std::thread::scope(|scope| {
+///     for index in 0..4_u64 {
+///         let arena = &arena;
+///         scope.spawn(move || {
+///             let values = arena.alloc_array(index, 16).unwrap();
+///             assert!(values.iter().all(|value| *value == index));
+///         });
+///     }
+/// });
+/// ```
+pub struct AtomicFixedArena<'a> {
+    base: *mut u8,
+    /// The layout the memory was allocated with, if the arena owns it
+    #[cfg(any(feature = "std", test))]
+    owned: Option<Layout>,
+    used: AtomicUsize,
+    padding: AtomicUsize,
+    capacity: usize,
+    buffer: PhantomData<&'a mut [MaybeUninit<u8>]>,
+}
+
+// The arena only hands out disjoint ranges of its memory, and `used` is only
+// changed atomically or through a mutable borrow
+unsafe impl Send for AtomicFixedArena<'_> {}
+unsafe impl Sync for AtomicFixedArena<'_> {}
+
+impl<'a> AtomicFixedArena<'a> {
+    /// Make a new atomic arena with a specified capacity and alignment
+    /// Uses the default system allocator to get the memory
+    /// Panics if the arena can not be created. See
+    /// `FixedArena::try_with_capacity` for the conditions that cause a
+    /// failure.
+    /// # Arguments
+    /// * `capacity` - The capacity of the arena in bytes
+    /// * `align` - The alginment to use for the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// ```
+    #[cfg(any(feature = "std", test))]
+    pub fn with_capacity(
+        capacity: usize,
+        align: usize,
+    ) -> AtomicFixedArena<'a> {
+        match AtomicFixedArena::try_with_capacity(capacity, align) {
+            Ok(arena) => arena,
+            Err(error) => panic!("Failed to create arena: {:?}", error),
+        }
+    }
+
+    /// Make a new atomic arena with a specified capacity and alignment
+    /// Uses the default system allocator to get the memory
+    /// Returns the same errors as `FixedArena::try_with_capacity`
+    /// # Arguments
+    /// * `capacity` - The capacity of the arena in bytes
+    /// * `align` - The alginment to use for the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::try_with_capacity(4096, 4).unwrap();
+    /// ```
+    #[cfg(any(feature = "std", test))]
+    pub fn try_with_capacity(
+        capacity: usize,
+        align: usize,
+    ) -> Result<AtomicFixedArena<'a>, AllocError> {
+        let (base, layout) = alloc_buffer(capacity, align)?;
+        Ok(AtomicFixedArena {
+            base,
+            owned: Some(layout),
+            used: AtomicUsize::new(0),
+            padding: AtomicUsize::new(0),
+            capacity,
+            buffer: PhantomData,
+        })
+    }
+
+    /// Make a new atomic arena that allocates from memory provided by the
+    /// caller. See `FixedArena::from_buffer`.
+    /// # Arguments
+    /// * `buffer` - The memory to allocate from. Its length is the capacity of
+    ///   the arena
+    /// # Examples
+    /// ```
+    /// # use core::mem::MaybeUninit;
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let mut buffer = [MaybeUninit::uninit(); 256];
+    /// let arena = AtomicFixedArena::from_buffer(&mut buffer);
+    /// ```
+    pub fn from_buffer(
+        buffer: &'a mut [MaybeUninit<u8>],
+    ) -> AtomicFixedArena<'a> {
+        AtomicFixedArena {
+            base: buffer.as_mut_ptr() as *mut u8,
+            #[cfg(any(feature = "std", test))]
+            owned: None,
+            used: AtomicUsize::new(0),
+            padding: AtomicUsize::new(0),
+            capacity: buffer.len(),
+            buffer: PhantomData,
+        }
+    }
+
+    /// Get a pointer to available memory and update the used attribute
+    /// Use a layout to determine how much to update the used attribute by.
+    /// Each thread computes the aligned range after the value of `used` it
+    /// saw, and only claims it if `used` has not changed in the meantime.
+    /// The ranges handed out never overlap, and nothing else is published
+    /// through `used`, so relaxed ordering is enough.
+    fn get_alloc_ptr_with_layout(
+        &self,
+        layout: Layout,
+    ) -> Result<*mut u8, AllocError> {
+        let mut used = self.used.load(Ordering::Relaxed);
+        loop {
+            let address = (self.base as usize).wrapping_add(used);
+            let skipped = address.wrapping_neg() & (layout.align() - 1);
+            let new_used = used
+                .checked_add(skipped)
+                .and_then(|aligned| aligned.checked_add(layout.size()))
+                .filter(|new_used| *new_used <= self.capacity);
+            let new_used = match new_used {
+                Some(new_used) => new_used,
+                None => {
+                    return Err(AllocError::AtCapacity {
+                        requested: layout.size(),
+                        align: layout.align(),
+                        remaining: self.capacity - used,
+                        top_callers: TopCallers::NONE,
+                    })
+                }
+            };
+            match self.used.compare_exchange_weak(
+                used,
+                new_used,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => {
+                    self.padding.fetch_add(skipped, Ordering::Relaxed);
+                    return Ok(unsafe { self.base.add(used + skipped) });
+                }
+                Err(actual) => used = actual,
+            }
+        }
+    }
+
+    /// Allocate and initialize a single instance of a data structure.
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc(5).unwrap();
+    /// assert_eq!(*result, 5);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, val: T) -> Result<&mut T, AllocError> {
+        let pointer = self.get_alloc_ptr_with_layout(Layout::new::<T>())?;
+        unsafe {
+            let result = pointer as *mut T;
+            ptr::write(result, val);
+            Ok(&mut *result)
+        }
+    }
+
+    /// Allocate and initialize a single instance of a data structure. It is
+    /// initialized with a value of 0
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_zeroed::<i32>().unwrap();
+    /// assert_eq!(*result, 0);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_zeroed<T>(&self) -> Result<&mut T, AllocError>
+    where
+        T: Zeroable,
+    {
+        let pointer = self.get_alloc_ptr_with_layout(Layout::new::<T>())?;
+        unsafe {
+            let result = pointer as *mut T;
+            ptr::write_bytes(result, 0, 1);
+            Ok(&mut *result)
+        }
+    }
+
+    /// Allocates an array of type T with count elements. The initial value of
+    /// the elements in the array is val.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `val` - the value to initialize the elements in the array to
+    /// * `count` - the number of elements to allocate for the array
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_array(1, 5).unwrap();
+    /// assert_eq!(result, [1, 1, 1, 1, 1]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_array<T>(
+        &self,
+        val: T,
+        count: usize,
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Clone,
+    {
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout(layout)? as *mut T;
+        unsafe {
+            for index in 0..count {
+                ptr::write(pointer.add(index), val.clone());
+            }
+            Ok(slice::from_raw_parts_mut(pointer, count))
+        }
+    }
+
+    /// Allocates an array of type `T` with count elements. The initial value of
+    /// the elements in the array is 0.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate
+    /// * `count` - the number of elements to allocate for the array
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_zeroed_array::<i32>(5).unwrap();
+    /// assert_eq!(result, [0, 0, 0, 0, 0]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_zeroed_array<T>(
+        &self,
+        count: usize,
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Zeroable,
+    {
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout(layout)? as *mut T;
+        unsafe {
+            ptr::write_bytes(pointer, 0, count);
+            Ok(slice::from_raw_parts_mut(pointer, count))
+        }
+    }
+
+    /// Resets the arena. The `used` value is set to 0, and any data allocated
+    /// since the last reset cannot be used
+    /// This mutably borrows self, so every thread must be done with its
+    /// allocations before the arena can be reset.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::atomic::AtomicFixedArena;
+    /// let mut arena = AtomicFixedArena::with_capacity(4096, 4);
+    /// arena.alloc_zeroed_array::<i32>(1024).unwrap();
+    /// arena.reset();
+    /// arena.alloc_zeroed_array::<i32>(1024).unwrap();
+    /// ```
+    pub fn reset(&mut self) {
+        #[cfg(feature = "debug-poison")]
+        unsafe {
+            let used = self.used.load(Ordering::Relaxed);
+            crate::poison::fill(self.base, used, crate::poison::FREED);
+        }
+        self.used.store(0, Ordering::Relaxed);
+        self.padding.store(0, Ordering::Relaxed);
+    }
+
+    /// The number of bytes that have been skipped to satisfy the alignment of
+    /// allocations since the last reset
+    pub fn padding(&self) -> usize {
+        self.padding.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for AtomicFixedArena<'_> {
+    /// Frees the memory of the arena if it was allocated by the arena
+    fn drop(&mut self) {
+        #[cfg(any(feature = "std", test))]
+        if let Some(layout) = self.owned {
+            unsafe {
+                dealloc(self.base, layout);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    use super::*;
+    use std::{mem::size_of, thread, vec::Vec};
+
+    use crate::test_common::{I32Struct, LargerStruct, SmallStruct};
+
+    const DEFAULT_ALIGN: usize = 8;
+    const THREAD_COUNT: usize = 8;
+
+    /// Test allocating from a single thread
+    #[test]
+    fn alloc() {
+        let arena = AtomicFixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let small = arena.alloc(SmallStruct { x: 1, y: 2 }).unwrap();
+        let larger = arena.alloc_zeroed::<LargerStruct>().unwrap();
+        let array = arena.alloc_array(I32Struct { x: 1, y: -1 }, 4).unwrap();
+        assert_eq!(*small, SmallStruct { x: 1, y: 2 });
+        assert_eq!(*larger, LargerStruct { x: 0, y: 0 });
+        assert!((larger as *const LargerStruct).is_aligned());
+        assert_eq!(array[3], I32Struct { x: 1, y: -1 });
+        assert_eq!(arena.padding(), 6);
+    }
+
+    /// Test that threads allocating at the same time get disjoint memory
+    #[test]
+    fn threads_disjoint() {
+        let count = 64;
+        let capacity = THREAD_COUNT * count * size_of::<usize>();
+        let arena = AtomicFixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+        thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREAD_COUNT)
+                .map(|id| {
+                    let arena = &arena;
+                    scope.spawn(move || {
+                        let mut values = Vec::new();
+                        for _ in 0..count {
+                            values.push(arena.alloc(id).unwrap());
+                        }
+                        values
+                    })
+                })
+                .collect();
+            for (id, handle) in handles.into_iter().enumerate() {
+                let values = handle.join().unwrap();
+                assert!(values.iter().all(|value| **value == id));
+            }
+        });
+        assert_eq!(arena.used.load(Ordering::Relaxed), capacity);
+    }
+
+    /// Test that capacity is enforced exactly when threads race for the
+    /// last of the memory
+    #[test]
+    fn threads_at_capacity() {
+        let capacity = 100 * size_of::<u64>();
+        let arena = AtomicFixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+        let successes: usize = thread::scope(|scope| {
+            let handles: Vec<_> = (0..THREAD_COUNT)
+                .map(|_| {
+                    let arena = &arena;
+                    scope.spawn(move || {
+                        (0..100).filter(|_| arena.alloc(0_u64).is_ok()).count()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).sum()
+        });
+        assert_eq!(successes, 100);
+        assert_eq!(arena.used.load(Ordering::Relaxed), capacity);
+    }
+
+    /// Test resetting after filling the arena
+    #[test]
+    fn reset() {
+        let mut arena = AtomicFixedArena::with_capacity(64, DEFAULT_ALIGN);
+        arena.alloc_zeroed_array::<u64>(8).unwrap();
+        assert!(matches!(
+            arena.alloc(1_u8),
+            Err(AllocError::AtCapacity { .. })
+        ));
+        arena.reset();
+        arena.alloc_zeroed_array::<u64>(8).unwrap();
+    }
+
+    /// Test sharing an arena over a caller provided buffer
+    #[test]
+    fn from_buffer() {
+        let mut buffer = [MaybeUninit::uninit(); 256];
+        let arena = AtomicFixedArena::from_buffer(&mut buffer);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| arena.alloc_zeroed_array::<u8>(64).unwrap());
+            }
+        });
+        assert!(arena.alloc(1_u8).is_err());
+    }
+}
+
+#[cfg(all(test, loom))]
+mod loom_tests {
+    use super::*;
+    use loom::{sync::Arc, thread};
+
+    /// Two threads allocating at the same time get disjoint memory
+    #[test]
+    fn concurrent_alloc_disjoint() {
+        loom::model(|| {
+            let arena = Arc::new(AtomicFixedArena::with_capacity(16, 8));
+            let other = arena.clone();
+            let handle = thread::spawn(move || {
+                let value = other.alloc(1_u64).unwrap();
+                value as *mut u64 as usize
+            });
+            let value = arena.alloc(2_u64).unwrap();
+            let address = value as *mut u64 as usize;
+            let other_address = handle.join().unwrap();
+
+            assert_ne!(address, other_address);
+            assert_eq!(*value, 2);
+            assert_eq!(arena.used.load(Ordering::Relaxed), 16);
+        });
+    }
+
+    /// Only as many allocations as fit succeed, whatever the interleaving
+    #[test]
+    fn concurrent_alloc_capacity() {
+        loom::model(|| {
+            let arena = Arc::new(AtomicFixedArena::with_capacity(24, 8));
+            let handles: std::vec::Vec<_> = (0..2)
+                .map(|_| {
+                    let arena = arena.clone();
+                    thread::spawn(move || {
+                        (0..2).filter(|_| arena.alloc(0_u64).is_ok()).count()
+                    })
+                })
+                .collect();
+            let successes: usize =
+                handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+            assert_eq!(successes, 3);
+            assert_eq!(arena.used.load(Ordering::Relaxed), 24);
+        });
+    }
+
+    /// Padding is claimed along with the allocation, so capacity is never
+    /// exceeded by an allocation that had to be aligned
+    #[test]
+    fn concurrent_alloc_mixed_alignment() {
+        loom::model(|| {
+            let arena = Arc::new(AtomicFixedArena::with_capacity(12, 8));
+            let other = arena.clone();
+            let handle = thread::spawn(move || other.alloc(1_u8).is_ok());
+            let wide = arena.alloc(2_u64).is_ok();
+            let narrow = handle.join().unwrap();
+
+            // the u64 only fits if it was allocated before the u8
+            assert!(narrow);
+            let used = arena.used.load(Ordering::Relaxed);
+            if wide {
+                assert_eq!(used, 9);
+            } else {
+                assert_eq!(used, 1);
+            }
+        });
+    }
+}
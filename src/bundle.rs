@@ -0,0 +1,190 @@
+use core::ptr::NonNull;
+
+use crate::{errors::AllocError, FixedArena};
+
+/// An arena bundled with a root value allocated in it, so that the two can be
+/// moved together, for example from a loader thread to the thread that uses
+/// the data. The root is reached through the bundle, which owns the arena, so
+/// it can't outlive the memory it lives in.
+/// The root can't borrow from the arena itself. Data reachable from it has to
+/// be stored in the root by value, like the elements of a slice.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{bundle::ArenaBundle, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let bundle = ArenaBundle::new(arena, |arena| {
+///     arena.alloc_array(1.5_f32, 256)
+/// })
+/// .unwrap();
+/// let sum = std::thread::spawn(move || bundle.get().iter().sum::<f32>())
+///     .join()
+///     .unwrap();
+/// assert_eq!(sum, 384.0);
+/// ```
+pub struct ArenaBundle<R: ?Sized> {
+    root: NonNull<R>,
+    arena: FixedArena<'static>,
+}
+
+// The root is only reachable through the bundle, and the arena is `Send`
+unsafe impl<R: ?Sized + Send> Send for ArenaBundle<R> {}
+
+impl<R: ?Sized> ArenaBundle<R> {
+    /// Bundle an arena with a root value. `build` allocates the root in the
+    /// arena, along with anything else it needs.
+    /// # Arguments
+    /// * `arena` - The arena to store the root in
+    /// * `build` - Allocates the root from the arena it is given
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{bundle::ArenaBundle, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let bundle = ArenaBundle::new(arena, |arena| arena.alloc(5_u64));
+    /// assert_eq!(*bundle.unwrap().get(), 5);
+    /// ```
+    pub fn new<F>(
+        arena: FixedArena<'static>,
+        build: F,
+    ) -> Result<ArenaBundle<R>, AllocError>
+    where
+        F: for<'b> FnOnce(
+            &'b FixedArena<'static>,
+        ) -> Result<&'b mut R, AllocError>,
+    {
+        let root = NonNull::from(build(&arena)?);
+        Ok(ArenaBundle { root, arena })
+    }
+
+    /// Get a reference to the root value
+    pub fn get(&self) -> &R {
+        unsafe { self.root.as_ref() }
+    }
+
+    /// Get a mutable reference to the root value
+    pub fn get_mut(&mut self) -> &mut R {
+        unsafe { self.root.as_mut() }
+    }
+
+    /// Give up the root and get the arena back. Destructors registered for the
+    /// root still run when the arena is reset or dropped.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{bundle::ArenaBundle, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let bundle = ArenaBundle::new(arena, |arena| arena.alloc(5_u64));
+    /// let mut arena = bundle.unwrap().into_arena();
+    /// arena.reset();
+    /// ```
+    pub fn into_arena(self) -> FixedArena<'static> {
+        self.arena
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{string::String, sync::Arc, thread};
+
+    use crate::test_common::I32Struct;
+
+    const DEFAULT_ALIGN: usize = 8;
+
+    /// Test moving an arena to another thread and allocating there
+    #[test]
+    fn send_arena() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc(I32Struct { x: 1, y: 2 }).unwrap();
+        let arena = thread::spawn(move || {
+            let value = arena.alloc(I32Struct { x: 3, y: 4 }).unwrap();
+            assert_eq!(*value, I32Struct { x: 3, y: 4 });
+            arena
+        })
+        .join()
+        .unwrap();
+        assert_eq!(arena.used.get(), 16);
+    }
+
+    /// Test that registered destructors run when the arena is dropped on the
+    /// thread it was sent to
+    #[test]
+    fn send_arena_with_drops() {
+        let shared = Arc::new(());
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc_with_drop(String::from("loaded")).unwrap();
+        arena.alloc_with_drop(shared.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        thread::spawn(move || drop(arena)).join().unwrap();
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    /// Test moving an arena over a borrowed buffer to another thread
+    #[test]
+    fn send_borrowed_arena() {
+        let mut buffer = [core::mem::MaybeUninit::uninit(); 64];
+        let arena = FixedArena::from_buffer(&mut buffer);
+        thread::scope(|scope| {
+            scope.spawn(move || {
+                assert_eq!(*arena.alloc(5_u32).unwrap(), 5);
+            });
+        });
+    }
+
+    /// Test building a bundle on one thread and using it on another
+    #[test]
+    fn bundle_across_threads() {
+        let bundle = thread::spawn(|| {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            ArenaBundle::new(arena, |arena| {
+                let values = arena.alloc_zeroed_array::<I32Struct>(16)?;
+                for (index, value) in values.iter_mut().enumerate() {
+                    value.x = index as i32;
+                }
+                Ok(values)
+            })
+            .unwrap()
+        })
+        .join()
+        .unwrap();
+
+        let mut bundle = thread::spawn(move || {
+            let mut bundle = bundle;
+            for value in bundle.get_mut() {
+                value.y = -value.x;
+            }
+            bundle
+        })
+        .join()
+        .unwrap();
+
+        for (index, value) in bundle.get().iter().enumerate() {
+            assert_eq!(
+                *value,
+                I32Struct {
+                    x: index as i32,
+                    y: -(index as i32)
+                }
+            );
+        }
+        assert_eq!(bundle.get_mut().len(), 16);
+    }
+
+    /// Test that a failure to build the root is returned
+    #[test]
+    fn bundle_at_capacity() {
+        let arena = FixedArena::with_capacity(16, DEFAULT_ALIGN);
+        let result = ArenaBundle::new(arena, |arena| arena.alloc([0_u64; 4]));
+        assert!(matches!(result, Err(AllocError::AtCapacity { .. })));
+    }
+
+    /// Test taking the arena back from a bundle
+    #[test]
+    fn into_arena() {
+        let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+        let bundle = ArenaBundle::new(arena, |arena| arena.alloc(1_u64));
+        let mut arena = bundle.unwrap().into_arena();
+        assert_eq!(arena.used.get(), 8);
+        arena.reset();
+        assert_eq!(arena.used.get(), 0);
+    }
+}
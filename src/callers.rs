@@ -0,0 +1,204 @@
+//! Allocation totals per call site of a `FixedArena`, recorded by the
+//! `track-callers` feature so that an arena that runs out of space can be
+//! traced back to the code that used it
+
+use core::{cmp::Reverse, fmt, panic::Location};
+
+use crate::FixedArena;
+
+/// The number of call sites an arena keeps totals for. Allocations from
+/// later call sites are only counted in `CallerReport::other_bytes` and
+/// `other_allocations`.
+pub const MAX_CALL_SITES: usize = 32;
+
+/// The number of call sites listed in `AllocError::AtCapacity`
+pub const TOP_CALLERS: usize = 3;
+
+/// The memory allocated from one place in the source since the arena was
+/// last reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallSite {
+    /// Where the allocation method was called
+    pub location: &'static Location<'static>,
+    /// The bytes of the arena taken by allocations from here, including
+    /// padding
+    pub bytes: usize,
+    /// The number of allocations made from here
+    pub allocations: usize,
+}
+
+impl fmt::Display for CallSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} bytes in {} allocations",
+            self.location, self.bytes, self.allocations
+        )
+    }
+}
+
+/// Who the memory of an allocation is counted for
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Caller {
+    /// The code that called an allocation method or formatted into an arena
+    Location(&'static Location<'static>),
+    /// A standard container, through `Allocator for &FixedArena`, which can't
+    /// pass on the code that called it
+    Allocator,
+}
+
+/// Counts the allocations of an arena for a caller until it is dropped. See
+/// `FixedArena::record_for`.
+pub(crate) struct CallerGuard<'s, 'a> {
+    pub(crate) arena: &'s FixedArena<'a>,
+    /// The caller that was counted for before the guard was made
+    pub(crate) outer: Option<Caller>,
+}
+
+impl Drop for CallerGuard<'_, '_> {
+    fn drop(&mut self) {
+        self.arena.caller.set(self.outer);
+    }
+}
+
+/// The call sites that allocated from an arena, from `FixedArena::report`.
+/// Memory given back by `rollback` or a dropped scope still counts for the
+/// call site that allocated it, until the arena is reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallerReport {
+    sites: [Option<CallSite>; MAX_CALL_SITES],
+    /// The bytes allocated from call sites after the first `MAX_CALL_SITES`
+    pub other_bytes: usize,
+    /// The number of allocations from call sites after the first
+    /// `MAX_CALL_SITES`
+    pub other_allocations: usize,
+    /// The bytes allocated through the `Allocator` impl of `&FixedArena`.
+    /// Standard containers don't pass on their caller, so these are kept
+    /// apart from the call sites instead of counting for one line of this
+    /// crate.
+    pub allocator_bytes: usize,
+    /// The number of allocations through the `Allocator` impl
+    pub allocator_allocations: usize,
+}
+
+impl CallerReport {
+    /// Make a report with no call sites
+    pub(crate) const fn new() -> CallerReport {
+        CallerReport {
+            sites: [None; MAX_CALL_SITES],
+            other_bytes: 0,
+            other_allocations: 0,
+            allocator_bytes: 0,
+            allocator_allocations: 0,
+        }
+    }
+
+    /// Add `bytes` and `allocations` to the totals of `caller`
+    pub(crate) fn record(
+        &mut self,
+        caller: Caller,
+        bytes: usize,
+        allocations: usize,
+    ) {
+        let location = match caller {
+            Caller::Location(location) => location,
+            Caller::Allocator => {
+                self.allocator_bytes += bytes;
+                self.allocator_allocations += allocations;
+                return;
+            }
+        };
+        for slot in self.sites.iter_mut() {
+            match slot {
+                Some(site) if site.location == location => {
+                    site.bytes += bytes;
+                    site.allocations += allocations;
+                    return;
+                }
+                Some(_) => {}
+                None => {
+                    *slot = Some(CallSite {
+                        location,
+                        bytes,
+                        allocations,
+                    });
+                    return;
+                }
+            }
+        }
+        self.other_bytes += bytes;
+        self.other_allocations += allocations;
+    }
+
+    /// Take `bytes` and `allocations` that were given back from the totals of
+    /// `caller`
+    pub(crate) fn release(
+        &mut self,
+        caller: Caller,
+        bytes: usize,
+        allocations: usize,
+    ) {
+        let location = match caller {
+            Caller::Location(location) => location,
+            Caller::Allocator => {
+                self.allocator_bytes =
+                    self.allocator_bytes.saturating_sub(bytes);
+                return;
+            }
+        };
+        let mut sites = self.sites.iter_mut().flatten();
+        if let Some(site) = sites.find(|site| site.location == location) {
+            site.bytes = site.bytes.saturating_sub(bytes);
+            site.allocations = site.allocations.saturating_sub(allocations);
+        }
+    }
+
+    /// Add the totals of another report to this one
+    pub(crate) fn add(&mut self, other: &CallerReport) {
+        for site in other.iter() {
+            let caller = Caller::Location(site.location);
+            self.record(caller, site.bytes, site.allocations);
+        }
+        self.other_bytes += other.other_bytes;
+        self.other_allocations += other.other_allocations;
+        self.allocator_bytes += other.allocator_bytes;
+        self.allocator_allocations += other.allocator_allocations;
+    }
+
+    /// Order the call sites from the most bytes to the fewest
+    pub(crate) fn sort(&mut self) {
+        self.sites.sort_unstable_by_key(|site| {
+            Reverse(site.map(|site| (site.bytes, site.allocations)))
+        });
+    }
+
+    /// The call sites, from the one that allocated the most bytes to the one
+    /// that allocated the fewest
+    pub fn iter(&self) -> impl Iterator<Item = &CallSite> {
+        self.sites.iter().flatten()
+    }
+}
+
+impl fmt::Display for CallerReport {
+    /// Writes one call site per line
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for site in self.iter() {
+            writeln!(f, "{}", site)?;
+        }
+        if self.allocator_allocations > 0 {
+            writeln!(
+                f,
+                "via Allocator: {} bytes in {} allocations",
+                self.allocator_bytes, self.allocator_allocations
+            )?;
+        }
+        if self.other_allocations > 0 {
+            writeln!(
+                f,
+                "other call sites: {} bytes in {} allocations",
+                self.other_bytes, self.other_allocations
+            )?;
+        }
+        Ok(())
+    }
+}
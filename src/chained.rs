@@ -0,0 +1,411 @@
+use core::{alloc::Layout, cell::Cell, ptr, slice};
+use std::{cell::RefCell, vec::Vec};
+
+#[cfg(feature = "track-callers")]
+use crate::callers::CallerReport;
+use crate::{errors::AllocError, FixedArena};
+
+/// An arena that allocates from a `FixedArena` until it is full, and then
+/// from overflow arenas on the heap instead of returning
+/// `AllocError::AtCapacity`. Each overflow arena has the capacity of the
+/// first arena, or more for an allocation that doesn't fit in that.
+/// Allocations from the first arena take the same fast path as in a
+/// `FixedArena`. `overflows` and `overflow_bytes` count the allocations that
+/// didn't fit, to help pick a capacity for the first arena. Needs the `std`
+/// feature.
+/// The stats of `primary` only cover the first arena: an allocation that
+/// overflows is not counted there as a failure. `report` lists the call
+/// sites of the first arena and the overflow arenas together.
+/// Like `FixedArena::alloc`, allocated values are not dropped.
+///
+/// Only part of the `FixedArena` API is provided: `alloc`, `alloc_with`,
+/// `alloc_array`, `alloc_slice_copy` and `alloc_str`. There is no
+/// `try_alloc_with`, `alloc_with_drop`, `alloc_zeroed*`, `alloc_uninit*`,
+/// `alloc_slice_clone`, `alloc_slice_fill_with`, `alloc_from_iter`, handles,
+/// checkpoints or scopes, and `ArenaVec`, `ArenaString` and the `Allocator`
+/// impl need a `FixedArena`. Use `primary` to get at the first arena for
+/// those.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::chained::ChainedArena;
+/// let arena = ChainedArena::with_capacity(64, 8);
+/// let values: Vec<&mut u64> =
+///     (0..16).map(|value| arena.alloc(value).unwrap()).collect();
+/// assert_eq!(*values[15], 15);
+/// assert_eq!(arena.overflows(), 8);
+/// ```
+pub struct ChainedArena<'a> {
+    primary: FixedArena<'a>,
+    /// The overflow arenas, oldest first. Only the last one is allocated
+    /// from. Allocations stay valid when the vector grows, since each arena
+    /// owns its memory on the heap.
+    overflow: RefCell<Vec<FixedArena<'static>>>,
+    overflows: Cell<usize>,
+    overflow_bytes: Cell<usize>,
+}
+
+impl<'a> ChainedArena<'a> {
+    /// Make a new chained arena that first allocates from `primary`
+    /// # Arguments
+    /// * `primary` - The arena to allocate from until it is full
+    /// # Examples
+    /// ```
+    /// # use core::mem::MaybeUninit;
+    /// # use tea_fixed_arena::{chained::ChainedArena, FixedArena};
+    /// let mut buffer = [MaybeUninit::uninit(); 256];
+    /// let arena = ChainedArena::new(FixedArena::from_buffer(&mut buffer));
+    /// let value = arena.alloc(5_u64).unwrap();
+    /// assert_eq!(*value, 5);
+    /// ```
+    pub fn new(primary: FixedArena<'a>) -> ChainedArena<'a> {
+        ChainedArena {
+            primary,
+            overflow: RefCell::new(Vec::new()),
+            overflows: Cell::new(0),
+            overflow_bytes: Cell::new(0),
+        }
+    }
+
+    /// Make a new chained arena whose first arena has a specified capacity
+    /// and alignment
+    /// Panics if the first arena can not be created. See
+    /// `FixedArena::try_with_capacity` for the conditions that cause a
+    /// failure.
+    /// # Arguments
+    /// * `capacity` - The capacity of the first arena in bytes
+    /// * `align` - The alignment to use for the first arena
+    pub fn with_capacity(capacity: usize, align: usize) -> ChainedArena<'a> {
+        ChainedArena::new(FixedArena::with_capacity(capacity, align))
+    }
+
+    /// Get a pointer to memory for `layout` from the first arena, or from the
+    /// overflow arenas if it is full
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn get_alloc_ptr_with_layout<T: ?Sized>(
+        &self,
+        layout: Layout,
+    ) -> Result<*mut u8, AllocError> {
+        match self.primary.get_alloc_ptr_unless_full::<T>(layout) {
+            Err(AllocError::AtCapacity { .. }) => {}
+            result => return result,
+        }
+        let result = self.get_overflow_ptr::<T>(layout);
+        if result.is_ok() {
+            self.overflows.set(self.overflows.get() + 1);
+            self.overflow_bytes
+                .set(self.overflow_bytes.get() + layout.size());
+        }
+        result
+    }
+
+    /// Get a pointer to memory for `layout` from the last overflow arena, or
+    /// from a new one if it is full
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn get_overflow_ptr<T: ?Sized>(
+        &self,
+        layout: Layout,
+    ) -> Result<*mut u8, AllocError> {
+        let mut overflow = self.overflow.borrow_mut();
+        if let Some(arena) = overflow.last() {
+            match arena.get_alloc_ptr_unless_full::<T>(layout) {
+                Err(AllocError::AtCapacity { .. }) => {}
+                result => return result,
+            }
+        }
+        let capacity = self.primary.capacity().max(layout.size()).max(1);
+        let arena = FixedArena::try_with_capacity(capacity, layout.align())?;
+        let pointer = arena.get_alloc_ptr_with_layout::<T>(layout)?;
+        overflow.push(arena);
+        Ok(pointer)
+    }
+
+    /// Allocate and initialize a single instance of a data structure
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::chained::ChainedArena;
+    /// let arena = ChainedArena::with_capacity(8, 8);
+    /// let first = arena.alloc(1_u64).unwrap();
+    /// let second = arena.alloc(2_u64).unwrap();
+    /// assert_eq!((*first, *second), (1, 2));
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc<T>(&self, val: T) -> Result<&mut T, AllocError> {
+        let pointer =
+            self.get_alloc_ptr_with_layout::<T>(Layout::new::<T>())? as *mut T;
+        unsafe {
+            ptr::write(pointer, val);
+            Ok(&mut *pointer)
+        }
+    }
+
+    /// Allocate a single instance of a data structure, initialized with the
+    /// value returned by `f`
+    /// # Arguments
+    /// * `f` - Called to make the value once the memory has been allocated
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_with<T, F>(&self, f: F) -> Result<&mut T, AllocError>
+    where
+        F: FnOnce() -> T,
+    {
+        let pointer =
+            self.get_alloc_ptr_with_layout::<T>(Layout::new::<T>())? as *mut T;
+        unsafe {
+            ptr::write(pointer, f());
+            Ok(&mut *pointer)
+        }
+    }
+
+    /// Allocates an array of type `T` with `count` clones of `val`
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `val` - The value to initialize the elements to
+    /// * `count` - The number of elements
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_array<T>(
+        &self,
+        val: T,
+        count: usize,
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Clone,
+    {
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout::<[T]>(layout)? as *mut T;
+        unsafe {
+            for index in 0..count {
+                ptr::write(pointer.add(index), val.clone());
+            }
+            Ok(slice::from_raw_parts_mut(pointer, count))
+        }
+    }
+
+    /// Allocates a copy of a slice of `Copy` values
+    /// # Arguments
+    /// * `values` - the values to copy into the arena
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_slice_copy<T>(
+        &self,
+        values: &[T],
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Copy,
+    {
+        let layout = FixedArena::array_layout::<T>(values.len())?;
+        let pointer = self.get_alloc_ptr_with_layout::<[T]>(layout)? as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(values.as_ptr(), pointer, values.len());
+            Ok(slice::from_raw_parts_mut(pointer, values.len()))
+        }
+    }
+
+    /// Allocates a copy of a string
+    /// # Arguments
+    /// * `val` - the string to copy into the arena
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_str(&self, val: &str) -> Result<&mut str, AllocError> {
+        let bytes = self.alloc_slice_copy(val.as_bytes())?;
+        unsafe { Ok(core::str::from_utf8_unchecked_mut(bytes)) }
+    }
+
+    /// The number of allocations that didn't fit in the first arena
+    pub fn overflows(&self) -> usize {
+        self.overflows.get()
+    }
+
+    /// The total size in bytes of the allocations that didn't fit in the
+    /// first arena
+    pub fn overflow_bytes(&self) -> usize {
+        self.overflow_bytes.get()
+    }
+
+    /// The call sites that allocated from the first arena and the overflow
+    /// arenas since the arena was last reset, from the most bytes to the
+    /// fewest. See `FixedArena::report`. Needs the `track-callers` feature.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::chained::ChainedArena;
+    /// let arena = ChainedArena::with_capacity(8, 8);
+    /// for _ in 0..4 {
+    ///     arena.alloc(0_u64).unwrap();
+    /// }
+    /// let report = arena.report();
+    /// let site = report.iter().next().unwrap();
+    /// assert_eq!((site.bytes, site.allocations), (32, 4));
+    /// ```
+    #[cfg(feature = "track-callers")]
+    pub fn report(&self) -> CallerReport {
+        let mut report = *self.primary.callers.borrow();
+        for arena in self.overflow.borrow().iter() {
+            report.add(&arena.callers.borrow());
+        }
+        report.sort();
+        report
+    }
+
+    /// The first arena, which is allocated from until it is full
+    pub fn primary(&self) -> &FixedArena<'a> {
+        &self.primary
+    }
+
+    /// Resets the first arena and frees the overflow arenas. The overflow
+    /// counts are kept, so they cover every use of the arena.
+    pub fn reset(&mut self) {
+        self.primary.reset();
+        self.overflow.get_mut().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_common::{I32Struct, LargerStruct};
+
+    /// Test that allocations come from the first arena while it has space
+    #[test]
+    fn primary() {
+        let arena = ChainedArena::with_capacity(64, 8);
+        let value = arena.alloc(I32Struct { x: 1, y: 2 }).unwrap();
+        assert_eq!(*value, I32Struct { x: 1, y: 2 });
+        assert_eq!(arena.primary().used(), 8);
+        assert_eq!(arena.overflows(), 0);
+    }
+
+    /// Test that allocations continue on the heap when the first arena is
+    /// full, and that earlier values are untouched
+    #[test]
+    fn overflow() {
+        let arena = ChainedArena::with_capacity(16, 8);
+        let values: Vec<&mut u64> =
+            (0..10).map(|value| arena.alloc(value).unwrap()).collect();
+        for (expected, value) in values.iter().enumerate() {
+            assert_eq!(**value, expected as u64);
+        }
+        assert_eq!(arena.primary().used(), 16);
+        assert_eq!(arena.overflows(), 8);
+        assert_eq!(arena.overflow_bytes(), 64);
+        // 8 values fit in 4 overflow arenas of 16 bytes
+        assert_eq!(arena.overflow.borrow().len(), 4);
+    }
+
+    /// Test that an allocation larger than the first arena gets an overflow
+    /// arena of its size
+    #[test]
+    fn larger_than_primary() {
+        let arena = ChainedArena::with_capacity(8, 8);
+        let values = arena.alloc_array(LargerStruct { x: 1, y: 2 }, 4).unwrap();
+        assert_eq!(values.len(), 4);
+        assert!(values
+            .iter()
+            .all(|value| *value == LargerStruct { x: 1, y: 2 }));
+        assert_eq!(arena.overflows(), 1);
+    }
+
+    /// Test that overflow allocations are aligned
+    #[test]
+    fn overflow_alignment() {
+        let arena = ChainedArena::with_capacity(4, 4);
+        arena.alloc(1_u8).unwrap();
+        let value = arena.alloc(2_u128).unwrap();
+        assert_eq!(value as *mut u128 as usize % align_of::<u128>(), 0);
+        assert_eq!(*value, 2);
+    }
+
+    /// Test copying slices and strings across the first arena and the
+    /// overflow arenas
+    #[test]
+    fn slices() {
+        let arena = ChainedArena::with_capacity(8, 8);
+        let first = arena.alloc_str("fits").unwrap();
+        let second = arena.alloc_str("does not fit").unwrap();
+        let third = arena.alloc_slice_copy(&[1_u16, 2, 3]).unwrap();
+        assert_eq!((&*first, &*second), ("fits", "does not fit"));
+        assert_eq!(third, [1, 2, 3]);
+        // The slice fits in neither the first arena nor the overflow arena
+        // that holds the second string
+        assert_eq!(arena.overflows(), 2);
+        assert_eq!(arena.overflow.borrow().len(), 2);
+    }
+
+    /// Test that errors other than running out of space are returned
+    #[test]
+    fn layout_overflow() {
+        let arena = ChainedArena::with_capacity(8, 8);
+        match arena.alloc_array(0_u64, usize::MAX / 4) {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert_eq!(err, AllocError::LayoutOverflow),
+        };
+        assert_eq!(arena.overflows(), 0);
+    }
+
+    /// Test that an allocation the heap can't provide is returned as an error
+    /// and not counted as an overflow. Miri stops the program instead of
+    /// failing the allocation.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn out_of_memory() {
+        let arena = ChainedArena::with_capacity(8, 8);
+        match arena.alloc_array(0_u8, isize::MAX as usize) {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert_eq!(err, AllocError::OutOfMemory),
+        };
+        assert_eq!(arena.overflows(), 0);
+        assert_eq!(arena.overflow_bytes(), 0);
+        assert!(arena.overflow.borrow().is_empty());
+    }
+
+    /// Test that allocations that overflow are not counted as failures of the
+    /// first arena
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        let arena = ChainedArena::with_capacity(16, 8);
+        for value in 0..6 {
+            arena.alloc(value as u64).unwrap();
+        }
+        let stats = arena.primary().stats();
+        assert_eq!(stats.allocations, 2);
+        assert_eq!(stats.failed_allocations, 0);
+        assert_eq!(arena.overflows(), 4);
+    }
+
+    /// Test that the report lists the allocations of the overflow arenas
+    #[cfg(feature = "track-callers")]
+    #[test]
+    fn report() {
+        let arena = ChainedArena::with_capacity(16, 8);
+        let line = line!() + 2;
+        for value in 0..6 {
+            arena.alloc(value as u64).unwrap();
+        }
+        arena.alloc_str("overflow").unwrap();
+        let report = arena.report();
+        let sites: Vec<_> = report
+            .iter()
+            .map(|site| (site.location.line(), site.bytes, site.allocations))
+            .collect();
+        assert_eq!(sites, [(line, 48, 6), (line + 2, 8, 1)]);
+    }
+
+    /// Test that a reset frees the overflow arenas and keeps the counts
+    #[test]
+    fn reset() {
+        let mut arena = ChainedArena::with_capacity(8, 8);
+        arena.alloc(1_u64).unwrap();
+        arena.alloc(2_u64).unwrap();
+        arena.reset();
+        assert!(arena.overflow.borrow().is_empty());
+        assert_eq!(arena.primary().used(), 0);
+        assert_eq!(arena.overflows(), 1);
+
+        arena.alloc_with(|| 3_u64).unwrap();
+        assert_eq!(arena.overflows(), 1);
+    }
+}
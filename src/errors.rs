@@ -1,5 +1,170 @@
+use core::fmt;
+
+#[cfg(feature = "track-callers")]
+use crate::callers::{CallSite, TOP_CALLERS};
+
 /// Errors that may be returned from an attempt to allocate from an arena
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AllocError {
-    AtCapacity,
+    /// The arena does not have enough space left for the allocation
+    AtCapacity {
+        /// The size in bytes of the allocation that failed
+        requested: usize,
+        /// The alignment of the allocation that failed
+        align: usize,
+        /// The number of bytes left in the arena, not accounting for any
+        /// padding the allocation would have needed
+        remaining: usize,
+        /// The call sites that allocated the most bytes from the arena.
+        /// Empty without the `track-callers` feature.
+        top_callers: TopCallers,
+    },
+    /// The size of the requested allocation does not fit in an `isize`
+    LayoutOverflow,
+    /// The allocation to resize or free in place is not the most recent
+    /// allocation in the arena
+    NotLast,
+    /// The alignment given for the arena is not a power of two
+    InvalidAlignment,
+    /// An arena was requested with a capacity of 0 bytes
+    ZeroCapacity,
+    /// The capacity, rounded up to the alignment, does not fit in an `isize`
+    CapacityOverflow,
+    /// The system allocator could not provide memory for the arena
+    OutOfMemory,
+}
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocError::AtCapacity {
+                requested,
+                align,
+                remaining,
+                top_callers,
+            } => write!(
+                f,
+                "arena is at capacity: requested {} bytes aligned to {}, \
+                 but only {} bytes remain{}",
+                requested, align, remaining, top_callers
+            ),
+            AllocError::LayoutOverflow => {
+                write!(f, "allocation size does not fit in an isize")
+            }
+            AllocError::NotLast => {
+                write!(f, "allocation is not the most recent in the arena")
+            }
+            AllocError::InvalidAlignment => {
+                write!(f, "arena alignment is not a power of two")
+            }
+            AllocError::ZeroCapacity => {
+                write!(f, "arena capacity must be greater than 0")
+            }
+            AllocError::CapacityOverflow => {
+                write!(f, "arena capacity does not fit in an isize")
+            }
+            AllocError::OutOfMemory => {
+                write!(f, "system allocator could not provide the arena memory")
+            }
+        }
+    }
+}
+
+impl core::error::Error for AllocError {}
+
+/// The call sites that allocated the most bytes from a `FixedArena` since it
+/// was last reset, carried by `AllocError::AtCapacity` so that an arena that
+/// runs out of space shows who used it. Only filled in with the
+/// `track-callers` feature. The call sites are ignored when comparing
+/// errors, so an `AllocError` compares the same with or without the feature.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TopCallers {
+    #[cfg(feature = "track-callers")]
+    sites: [Option<CallSite>; TOP_CALLERS],
+}
+
+impl TopCallers {
+    /// No call sites, for errors that don't come from a `FixedArena`
+    pub const NONE: TopCallers = TopCallers {
+        #[cfg(feature = "track-callers")]
+        sites: [None; TOP_CALLERS],
+    };
+
+    /// Take the first call sites of a sorted list
+    #[cfg(feature = "track-callers")]
+    pub(crate) fn new<'s>(sites: impl Iterator<Item = &'s CallSite>) -> Self {
+        let mut top = TopCallers::NONE;
+        for (slot, site) in top.sites.iter_mut().zip(sites) {
+            *slot = Some(*site);
+        }
+        top
+    }
+
+    /// The call sites, from the one that allocated the most bytes to the one
+    /// that allocated the fewest. Needs the `track-callers` feature.
+    #[cfg(feature = "track-callers")]
+    pub fn iter(&self) -> impl Iterator<Item = &CallSite> {
+        self.sites.iter().flatten()
+    }
+}
+
+impl PartialEq for TopCallers {
+    fn eq(&self, _other: &TopCallers) -> bool {
+        true
+    }
+}
+
+impl Eq for TopCallers {}
+
+impl fmt::Display for TopCallers {
+    /// Writes `; top callers: ` and the call sites, or nothing if there are
+    /// none
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        #[cfg(feature = "track-callers")]
+        for (index, site) in self.iter().enumerate() {
+            let separator = if index == 0 { "; top callers:" } else { "," };
+            write!(f, "{} {}", separator, site)?;
+        }
+        #[cfg(not(feature = "track-callers"))]
+        let _ = f;
+        Ok(())
+    }
+}
+
+/// Errors that may be returned from an allocation that is initialized by a
+/// fallible closure, such as `FixedArena::try_alloc_with`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocOrInitError<E> {
+    /// The arena could not provide the memory for the value
+    Alloc(AllocError),
+    /// The closure that initializes the value returned an error
+    Init(E),
+}
+
+impl<E> From<AllocError> for AllocOrInitError<E> {
+    fn from(error: AllocError) -> Self {
+        AllocOrInitError::Alloc(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for AllocOrInitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AllocOrInitError::Alloc(error) => write!(f, "{}", error),
+            AllocOrInitError::Init(error) => {
+                write!(f, "failed to initialize value: {}", error)
+            }
+        }
+    }
+}
+
+impl<E: core::error::Error + 'static> core::error::Error
+    for AllocOrInitError<E>
+{
+    fn source(&self) -> Option<&(dyn core::error::Error + 'static)> {
+        match self {
+            AllocOrInitError::Alloc(error) => Some(error),
+            AllocOrInitError::Init(error) => Some(error),
+        }
+    }
 }
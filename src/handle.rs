@@ -0,0 +1,230 @@
+use core::{
+    fmt,
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// The source of arena generations. Every arena starts with a generation that
+/// no other arena has used, so a handle from one arena is never valid in
+/// another.
+static NEXT_GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Get a generation that has not been used by any arena
+pub(crate) fn next_generation() -> usize {
+    NEXT_GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// A reference to a value allocated with `FixedArena::alloc_handle`. A handle
+/// is an offset into the arena and the generation of the arena when the value
+/// was allocated, so unlike a reference it does not borrow the arena and can
+/// be stored anywhere.
+/// Resetting the arena, or rolling it back past the value, moves the arena to
+/// a new generation. `FixedArena::get` and `get_mut` return `None` for handles
+/// from an older generation.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{handle::ArenaHandle, FixedArena};
+/// struct Entity {
+///     health: u32,
+/// }
+///
+/// let mut arena = FixedArena::with_capacity(4096, 4);
+/// let entities: Vec<ArenaHandle<Entity>> = (0..4)
+///     .map(|_| arena.alloc_handle(Entity { health: 100 }).unwrap())
+///     .collect();
+/// arena.get_mut(entities[1]).unwrap().health -= 10;
+/// assert_eq!(arena.get(entities[1]).unwrap().health, 90);
+///
+/// arena.reset();
+/// assert!(arena.get(entities[1]).is_none());
+/// ```
+pub struct ArenaHandle<T> {
+    pub(crate) offset: usize,
+    pub(crate) generation: usize,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> ArenaHandle<T> {
+    /// Make a handle to the value at `offset` in an arena at `generation`
+    pub(crate) fn new(offset: usize, generation: usize) -> ArenaHandle<T> {
+        ArenaHandle {
+            offset,
+            generation,
+            value: PhantomData,
+        }
+    }
+}
+
+// Handles are plain data, so they are implemented by hand to avoid requiring
+// the same traits from `T`
+impl<T> Clone for ArenaHandle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaHandle<T> {}
+
+impl<T> PartialEq for ArenaHandle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset && self.generation == other.generation
+    }
+}
+
+impl<T> Eq for ArenaHandle<T> {}
+
+impl<T> Hash for ArenaHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.offset.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for ArenaHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArenaHandle")
+            .field("offset", &self.offset)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::AllocError,
+        test_common::{I32Struct, LargerStruct},
+        FixedArena,
+    };
+    use core::ptr::NonNull;
+    use std::{
+        collections::HashSet,
+        sync::{Arc, Mutex},
+    };
+
+    const DEFAULT_ALIGN: usize = 4;
+
+    /// Test reading and writing values through handles
+    #[test]
+    fn get() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let first = arena.alloc_handle(I32Struct { x: 1, y: 2 }).unwrap();
+        let second = arena.alloc_handle(LargerStruct { x: 3, y: 4 }).unwrap();
+
+        arena.get_mut(first).unwrap().x = 5;
+        assert_eq!(*arena.get(first).unwrap(), I32Struct { x: 5, y: 2 });
+        assert_eq!(*arena.get(second).unwrap(), LargerStruct { x: 3, y: 4 });
+    }
+
+    /// Test that handles are stale after a reset, even when the same offset
+    /// is allocated again
+    #[test]
+    fn stale_after_reset() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let old = arena.alloc_handle(1_u32).unwrap();
+        arena.reset();
+        let new = arena.alloc_handle(2_u32).unwrap();
+
+        assert_eq!(old.offset, new.offset);
+        assert!(arena.get(old).is_none());
+        assert!(arena.get_mut(old).is_none());
+        assert_eq!(*arena.get(new).unwrap(), 2);
+    }
+
+    /// Test that handles to values released by a rollback are stale
+    #[test]
+    fn stale_after_rollback() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let checkpoint = arena.checkpoint();
+        let old = arena.alloc_handle(1_u32).unwrap();
+        arena.rollback(checkpoint);
+        arena.alloc_handle(2_u32).unwrap();
+
+        assert!(arena.get(old).is_none());
+    }
+
+    /// Test that handles made before a scope stay valid when only plain
+    /// allocations were made in the scope
+    #[test]
+    fn valid_after_scope() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let handle = arena.alloc_handle(1_u32).unwrap();
+        {
+            let scope = arena.scope();
+            scope.alloc_array(0_u8, 64).unwrap();
+            assert_eq!(*scope.get(handle).unwrap(), 1);
+        }
+        assert_eq!(*arena.get(handle).unwrap(), 1);
+    }
+
+    /// Test that handles made in a scope are stale after the scope
+    #[test]
+    fn stale_after_scope() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let handle;
+        {
+            let scope = arena.scope();
+            handle = scope.alloc_handle(1_u32).unwrap();
+            assert_eq!(*scope.get(handle).unwrap(), 1);
+        }
+        assert!(arena.get(handle).is_none());
+    }
+
+    /// Test that a handle from one arena can't be used with another
+    #[test]
+    fn other_arena() {
+        let first = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let second = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let handle = first.alloc_handle(1_u32).unwrap();
+        second.alloc_handle(2_u32).unwrap();
+
+        assert!(second.get(handle).is_none());
+    }
+
+    /// Test that values behind handles are dropped with the arena
+    #[test]
+    fn drop_values() {
+        let shared = Arc::new(Mutex::new(0));
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc_handle(shared.clone()).unwrap();
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        arena.reset();
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    /// Test that a handle is stale after its value is popped, even when the
+    /// same offset is allocated again
+    #[test]
+    fn stale_after_pop() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let old = arena.alloc_handle(1_u32).unwrap();
+        let pointer = NonNull::from(arena.get(old).unwrap()).cast::<u8>();
+        unsafe { arena.pop_last(pointer, 4).unwrap() };
+        arena.alloc(2_u32).unwrap();
+
+        assert!(arena.get(old).is_none());
+    }
+
+    /// Test that running out of space returns an error
+    #[test]
+    fn at_capacity() {
+        let arena = FixedArena::with_capacity(8, 8);
+        arena.alloc_handle(1_u64).unwrap();
+        match arena.alloc_handle(2_u32) {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert!(matches!(err, AllocError::AtCapacity { .. })),
+        };
+    }
+
+    /// Test that handles can be stored in collections
+    #[test]
+    fn hash() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let first = arena.alloc_handle(1_u32).unwrap();
+        let second = arena.alloc_handle(2_u32).unwrap();
+        let handles: HashSet<_> = [first, second, first].into_iter().collect();
+        assert_eq!(handles.len(), 2);
+    }
+}
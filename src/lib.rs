@@ -1,36 +1,201 @@
-#![feature(test)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![feature(allocator_api)]
+#![feature(cfg_sanitize)]
+#![cfg_attr(test, feature(btreemap_alloc))]
+#![cfg_attr(test, feature(test))]
+#[cfg(test)]
 extern crate test;
-
-// TODO: no STD this library
-
+// Lets `#[derive(Zeroable)]` refer to this crate by name from inside it
+extern crate self as tea_fixed_arena;
+
+mod allocator;
+mod asan;
+pub mod atomic;
+pub mod bundle;
+#[cfg(feature = "track-callers")]
+pub mod callers;
+#[cfg(any(feature = "std", test))]
+pub mod chained;
 pub mod errors;
+pub mod handle;
+#[cfg(feature = "debug-poison")]
+pub mod poison;
+#[cfg(feature = "red-zone")]
+pub mod red_zone;
+pub mod scope;
+pub mod stack;
+#[cfg(feature = "stats")]
+pub mod stats;
+pub mod string;
+pub mod typed;
+pub mod uninit;
+pub mod vec;
+pub mod zeroable;
 
 #[cfg(test)]
+#[allow(clippy::neg_multiply)]
 mod bench_bumpalo;
 #[cfg(test)]
+#[allow(clippy::box_default, clippy::neg_multiply)]
 mod bench_std;
 #[cfg(test)]
 mod test_common;
 
-use core::{cell::Cell, ptr};
-use std::{
-    alloc::{alloc, dealloc, Layout},
+use core::{
+    alloc::Layout,
+    cell::Cell,
+    marker::PhantomData,
+    mem::{self, MaybeUninit},
+    ptr::{self, NonNull},
     slice,
 };
+#[cfg(feature = "track-callers")]
+use core::{cell::RefCell, panic::Location};
+#[cfg(any(feature = "std", test))]
+use std::alloc::{alloc, dealloc};
+
+use crate::{
+    errors::{AllocError, AllocOrInitError, TopCallers},
+    handle::{next_generation, ArenaHandle},
+    scope::{ArenaScope, Checkpoint},
+    vec::ArenaVec,
+    zeroable::Zeroable,
+};
+#[cfg(feature = "track-callers")]
+use callers::{Caller, CallerGuard, CallerReport};
+#[cfg(feature = "red-zone")]
+use red_zone::{Overrun, RedZone, CANARY, CANARY_SIZE};
+#[cfg(feature = "stats")]
+use stats::ArenaStats;
+
+/// A bump allocator over a fixed block of memory. The memory either comes
+/// from the system allocator (`with_capacity`), in which case the arena owns
+/// it, or is borrowed from the caller for the lifetime `'a` (`from_buffer`).
+pub struct FixedArena<'a> {
+    /// The start of the memory. Only changes for the arena inside a
+    /// `StackArena`, which is moved along with its memory.
+    base: Cell<*mut u8>,
+    /// The layout the memory was allocated with, if the arena owns it
+    #[cfg(any(feature = "std", test))]
+    owned: Option<Layout>,
+    used: Cell<usize>,
+    padding: Cell<usize>,
+    capacity: usize,
+    drops: Cell<*mut DropEntry>,
+    /// The generation that handles made by `alloc_handle` must match
+    generation: Cell<usize>,
+    /// Identifies the arena since it was last reset, so that `rollback` can
+    /// ignore checkpoints made before the reset or by another arena
+    epoch: Cell<usize>,
+    /// The end offset of the most recent allocation made by `alloc_handle` in
+    /// the current generation, or 0 if there is none
+    handles_end: Cell<usize>,
+    /// Whether new allocations get a red zone, see `set_red_zones`
+    #[cfg(feature = "red-zone")]
+    red_zones_enabled: bool,
+    /// The record of the most recent allocation made with a red zone
+    #[cfg(feature = "red-zone")]
+    red_zones: Cell<*mut RedZone>,
+    #[cfg(feature = "stats")]
+    stats: Cell<ArenaStats>,
+    /// The memory allocated from each call site since the last reset
+    #[cfg(feature = "track-callers")]
+    callers: RefCell<CallerReport>,
+    /// Who allocations are counted for instead of the caller of the
+    /// allocation method, set by `record_for`
+    #[cfg(feature = "track-callers")]
+    caller: Cell<Option<Caller>>,
+    buffer: PhantomData<&'a mut [MaybeUninit<u8>]>,
+}
+
+// The arena can be moved to another thread along with its memory:
+// * The memory is either owned by the arena or borrowed mutably, and nothing
+//   else can reach it while the arena isn't borrowed.
+// * References to allocations borrow the arena, so none of them can be left
+//   behind on the old thread.
+// * The only values the arena itself touches later are the ones registered
+//   with `alloc_with_drop`, which requires them to be `Send`.
+// The arena is not `Sync`, because `used` is a `Cell`.
+unsafe impl Send for FixedArena<'_> {}
+
+/// A destructor registered by `alloc_with_drop`. Entries are stored in the
+/// arena right after the value they drop, and are linked from the most recent
+/// entry to the oldest
+struct DropEntry {
+    drop_fn: unsafe fn(*mut u8),
+    value: *mut u8,
+    next: *mut DropEntry,
+}
+
+/// Drop the value of type `T` stored at `value`
+unsafe fn drop_value<T>(value: *mut u8) {
+    ptr::drop_in_place(value as *mut T);
+}
 
-use crate::errors::AllocError;
+/// Allocate the memory for an arena from the system allocator. Returns the
+/// memory and the layout it must be freed with.
+#[cfg(any(feature = "std", test))]
+fn alloc_buffer(
+    capacity: usize,
+    align: usize,
+) -> Result<(*mut u8, Layout), AllocError> {
+    if !align.is_power_of_two() {
+        return Err(AllocError::InvalidAlignment);
+    }
+    if capacity == 0 {
+        return Err(AllocError::ZeroCapacity);
+    }
+    let layout = match Layout::from_size_align(capacity, align) {
+        Ok(layout) => layout,
+        Err(_) => return Err(AllocError::CapacityOverflow),
+    };
+    // layout has a non-zero size, so calling alloc is defined
+    let base = unsafe { alloc(layout) };
+    if base.is_null() {
+        return Err(AllocError::OutOfMemory);
+    }
+    Ok((base, layout))
+}
 
-pub struct FixedArena {
+/// Get a pointer to available memory in the `capacity` bytes at `base` and
+/// update `used`. The returned pointer is aligned to `layout.align()`, even
+/// when that is larger than the alignment of the memory itself. Any bytes
+/// skipped to reach that alignment are added to `padding`.
+fn bump(
     base: *mut u8,
-    base_align: usize,
-    used: Cell<usize>,
     capacity: usize,
+    used: &Cell<usize>,
+    padding: &Cell<usize>,
+    layout: Layout,
+) -> Result<*mut u8, AllocError> {
+    let start = used.get();
+    let address = (base as usize).wrapping_add(start);
+    let skipped = address.wrapping_neg() & (layout.align() - 1);
+    let new_used = start
+        .checked_add(skipped)
+        .and_then(|aligned| aligned.checked_add(layout.size()));
+    match new_used {
+        Some(new_used) if new_used <= capacity => {
+            let result: *mut u8 = unsafe { base.add(start + skipped) };
+            used.set(new_used);
+            padding.set(padding.get() + skipped);
+            Ok(result)
+        }
+        _ => Err(AllocError::AtCapacity {
+            requested: layout.size(),
+            align: layout.align(),
+            remaining: capacity - start,
+            top_callers: TopCallers::NONE,
+        }),
+    }
 }
 
 // TODO: inline functions?
-impl FixedArena {
+impl<'a> FixedArena<'a> {
     /// Make a new fixed arena with a specified capacity and alignment
     /// Uses the default system allocator to get the memory
+    /// Panics if the arena can not be created. See `try_with_capacity` for
+    /// the conditions that cause a failure.
     /// # Arguments
     /// * `capacity` - The capacity of the arena in bytes
     /// * `align` - The alginment to use for the arena
@@ -39,43 +204,497 @@ impl FixedArena {
     /// # use tea_fixed_arena::FixedArena;
     /// let arena = FixedArena::with_capacity(4096, 4);
     /// ```
-    pub fn with_capacity(capacity: usize, align: usize) -> FixedArena {
-        let layout = Layout::from_size_align(capacity, align)
-            .expect("Bad arguments for layout");
-        let base = unsafe { alloc(layout) };
+    #[cfg(any(feature = "std", test))]
+    pub fn with_capacity(capacity: usize, align: usize) -> FixedArena<'a> {
+        match FixedArena::try_with_capacity(capacity, align) {
+            Ok(arena) => arena,
+            Err(error) => panic!("Failed to create arena: {:?}", error),
+        }
+    }
+
+    /// Make a new fixed arena with a specified capacity and alignment
+    /// Uses the default system allocator to get the memory
+    /// Returns an error instead of panicking when the arena can not be
+    /// created:
+    /// * `AllocError::InvalidAlignment` if `align` is not a power of two
+    /// * `AllocError::ZeroCapacity` if `capacity` is 0
+    /// * `AllocError::CapacityOverflow` if `capacity` rounded up to `align`
+    ///   does not fit in an `isize`
+    /// * `AllocError::OutOfMemory` if the system allocator fails
+    /// # Arguments
+    /// * `capacity` - The capacity of the arena in bytes
+    /// * `align` - The alginment to use for the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{errors::AllocError, FixedArena};
+    /// let arena = FixedArena::try_with_capacity(4096, 4).unwrap();
+    /// match FixedArena::try_with_capacity(4096, 3) {
+    ///     Ok(_) => assert!(false),
+    ///     Err(error) => assert_eq!(error, AllocError::InvalidAlignment),
+    /// };
+    /// ```
+    #[cfg(any(feature = "std", test))]
+    pub fn try_with_capacity(
+        capacity: usize,
+        align: usize,
+    ) -> Result<FixedArena<'a>, AllocError> {
+        let (base, layout) = alloc_buffer(capacity, align)?;
+        asan::poison(base, capacity);
+        Ok(FixedArena {
+            base: Cell::new(base),
+            owned: Some(layout),
+            capacity,
+            used: Cell::new(0),
+            padding: Cell::new(0),
+            drops: Cell::new(ptr::null_mut()),
+            generation: Cell::new(next_generation()),
+            epoch: Cell::new(next_generation()),
+            handles_end: Cell::new(0),
+            #[cfg(feature = "red-zone")]
+            red_zones_enabled: false,
+            #[cfg(feature = "red-zone")]
+            red_zones: Cell::new(ptr::null_mut()),
+            #[cfg(feature = "stats")]
+            stats: Cell::new(ArenaStats::default()),
+            #[cfg(feature = "track-callers")]
+            callers: RefCell::new(CallerReport::new()),
+            #[cfg(feature = "track-callers")]
+            caller: Cell::new(None),
+            buffer: PhantomData,
+        })
+    }
+
+    /// Make a new fixed arena that allocates from memory provided by the
+    /// caller instead of the system allocator. The arena borrows the buffer
+    /// for as long as it exists, and does not free it when dropped. This is
+    /// the only way to make an arena without the `std` feature.
+    /// Allocations are aligned within the buffer, so the buffer itself can
+    /// have any alignment.
+    /// # Arguments
+    /// * `buffer` - The memory to allocate from. Its length is the capacity of
+    ///   the arena
+    /// # Examples
+    /// ```
+    /// # use core::mem::MaybeUninit;
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut buffer = [MaybeUninit::uninit(); 256];
+    /// let arena = FixedArena::from_buffer(&mut buffer);
+    /// let value = arena.alloc(5_u64).unwrap();
+    /// assert_eq!(*value, 5);
+    /// ```
+    pub fn from_buffer(buffer: &'a mut [MaybeUninit<u8>]) -> FixedArena<'a> {
+        asan::poison(buffer.as_ptr() as *const u8, buffer.len());
+        FixedArena {
+            base: Cell::new(buffer.as_mut_ptr() as *mut u8),
+            #[cfg(any(feature = "std", test))]
+            owned: None,
+            capacity: buffer.len(),
+            used: Cell::new(0),
+            padding: Cell::new(0),
+            drops: Cell::new(ptr::null_mut()),
+            generation: Cell::new(next_generation()),
+            epoch: Cell::new(next_generation()),
+            handles_end: Cell::new(0),
+            #[cfg(feature = "red-zone")]
+            red_zones_enabled: false,
+            #[cfg(feature = "red-zone")]
+            red_zones: Cell::new(ptr::null_mut()),
+            #[cfg(feature = "stats")]
+            stats: Cell::new(ArenaStats::default()),
+            #[cfg(feature = "track-callers")]
+            callers: RefCell::new(CallerReport::new()),
+            #[cfg(feature = "track-callers")]
+            caller: Cell::new(None),
+            buffer: PhantomData,
+        }
+    }
+
+    /// Make an arena of `capacity` bytes that has no memory yet, for a
+    /// `StackArena`, which sets `base` to its inline bytes before every use.
+    /// It is never given an `alloc_with_drop` value or a red zone, which
+    /// would keep pointers to where the stack arena used to be.
+    pub(crate) const fn unplaced(capacity: usize) -> FixedArena<'static> {
         FixedArena {
-            base,
-            base_align: align,
+            base: Cell::new(ptr::null_mut()),
+            #[cfg(any(feature = "std", test))]
+            owned: None,
             capacity,
             used: Cell::new(0),
+            padding: Cell::new(0),
+            drops: Cell::new(ptr::null_mut()),
+            // Handles are not made for a stack arena
+            generation: Cell::new(0),
+            epoch: Cell::new(0),
+            handles_end: Cell::new(0),
+            #[cfg(feature = "red-zone")]
+            red_zones_enabled: false,
+            #[cfg(feature = "red-zone")]
+            red_zones: Cell::new(ptr::null_mut()),
+            #[cfg(feature = "stats")]
+            stats: Cell::new(ArenaStats::new()),
+            #[cfg(feature = "track-callers")]
+            callers: RefCell::new(CallerReport::new()),
+            #[cfg(feature = "track-callers")]
+            caller: Cell::new(None),
+            buffer: PhantomData,
         }
     }
 
     /// Get a pointer to available memory and update the used attribute
-    /// Use a layout to determine how much to update the used attribute by
-    fn get_alloc_ptr_with_layout(
+    /// Use a layout to determine how much to update the used attribute by.
+    /// See `bump`. `T` is the type being allocated, which is recorded for
+    /// `verify` when red zones are enabled.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn get_alloc_ptr_with_layout<T: ?Sized>(
         &self,
         layout: Layout,
     ) -> Result<*mut u8, AllocError> {
-        let new_used = self.used.get() + layout.size();
-        if new_used <= self.capacity {
-            let result: *mut u8 =
-                unsafe { self.base.offset(self.used.get() as isize) };
-            self.used.set(new_used);
-            Ok(result)
+        let result = self.get_alloc_ptr_unless_full::<T>(layout);
+        #[cfg(feature = "stats")]
+        if result.is_err() {
+            self.record_alloc(false, self.padding.get());
+        }
+        #[cfg(feature = "track-callers")]
+        let result = result.map_err(|error| self.with_top_callers(error));
+        result
+    }
+
+    /// Get a pointer to memory for `layout` like `get_alloc_ptr_with_layout`,
+    /// but leave a failure out of the stats and without the top callers, for
+    /// a caller that allocates somewhere else when the arena is full
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub(crate) fn get_alloc_ptr_unless_full<T: ?Sized>(
+        &self,
+        layout: Layout,
+    ) -> Result<*mut u8, AllocError> {
+        #[cfg(feature = "stats")]
+        let padding = self.padding.get();
+        #[cfg(feature = "track-callers")]
+        let used = self.used.get();
+        let result = self.bump::<T>(layout);
+        #[cfg(feature = "stats")]
+        if result.is_ok() {
+            self.record_alloc(true, padding);
+        }
+        #[cfg(feature = "track-callers")]
+        if result.is_ok() {
+            self.record_caller(used);
+        }
+        result
+    }
+
+    /// Add the memory taken since `used` was read, as one allocation, to the
+    /// totals of the code that called the allocation method
+    #[cfg(feature = "track-callers")]
+    #[track_caller]
+    fn record_caller(&self, used: usize) {
+        let bytes = self.used.get() - used;
+        self.callers.borrow_mut().record(self.caller(), bytes, 1);
+    }
+
+    /// Fill in the call sites that allocated the most bytes in an
+    /// `AtCapacity` error
+    #[cfg(feature = "track-callers")]
+    fn with_top_callers(&self, mut error: AllocError) -> AllocError {
+        if let AllocError::AtCapacity { top_callers, .. } = &mut error {
+            *top_callers = TopCallers::new(self.report().iter());
+        }
+        error
+    }
+
+    /// Who the allocation being made is counted for: the code that called
+    /// the allocation method, unless `record_for` says otherwise
+    #[cfg(feature = "track-callers")]
+    #[track_caller]
+    fn caller(&self) -> Caller {
+        match self.caller.get() {
+            Some(caller) => caller,
+            None => Caller::Location(Location::caller()),
+        }
+    }
+
+    /// Count allocations for `caller` until the returned guard is dropped,
+    /// for code that can't pass its caller on with `#[track_caller]`
+    #[cfg(feature = "track-callers")]
+    pub(crate) fn record_for(&self, caller: Caller) -> CallerGuard<'_, 'a> {
+        CallerGuard {
+            arena: self,
+            outer: self.caller.replace(Some(caller)),
+        }
+    }
+
+    /// Get a pointer to available memory like `get_alloc_ptr_with_layout`,
+    /// without counting it as an allocation in the stats
+    // `T` is only used for the red zone record
+    #[cfg_attr(
+        not(feature = "red-zone"),
+        allow(clippy::extra_unused_type_parameters)
+    )]
+    fn bump<T: ?Sized>(&self, layout: Layout) -> Result<*mut u8, AllocError> {
+        #[cfg(feature = "red-zone")]
+        if self.red_zones_enabled {
+            return self.bump_red_zone(layout, core::any::type_name::<T>());
+        }
+        self.bump_plain(layout)
+    }
+
+    /// Get a pointer to available memory like `bump`, without a red zone
+    fn bump_plain(&self, layout: Layout) -> Result<*mut u8, AllocError> {
+        let pointer = bump(
+            self.base.get(),
+            self.capacity,
+            &self.used,
+            &self.padding,
+            layout,
+        )?;
+        asan::unpoison(pointer, layout.size());
+        Ok(pointer)
+    }
+
+    /// Reserve a red zone record, the allocation and its canary, in that
+    /// order, and link the record into the list of red zones. Leaves the
+    /// arena unchanged if they don't all fit.
+    #[cfg(feature = "red-zone")]
+    fn bump_red_zone(
+        &self,
+        layout: Layout,
+        type_name: &'static str,
+    ) -> Result<*mut u8, AllocError> {
+        let used = self.used.get();
+        let padding = self.padding.get();
+        let reserved =
+            self.bump_plain(Layout::new::<RedZone>())
+                .and_then(|record| {
+                    let pointer = self.bump_plain(layout)?;
+                    self.bump_plain(Layout::new::<[u8; CANARY_SIZE]>())?;
+                    Ok((record as *mut RedZone, pointer))
+                });
+        let (record, pointer) = match reserved {
+            Ok(reserved) => reserved,
+            Err(error) => {
+                asan::poison(
+                    unsafe { self.base.get().add(used) },
+                    self.used.get() - used,
+                );
+                self.used.set(used);
+                self.padding.set(padding);
+                // Report the allocation that was asked for rather than the
+                // record or canary that didn't fit
+                return Err(match error {
+                    AllocError::AtCapacity { .. } => AllocError::AtCapacity {
+                        requested: layout.size(),
+                        align: layout.align(),
+                        remaining: self.capacity - used,
+                        top_callers: TopCallers::NONE,
+                    },
+                    error => error,
+                });
+            }
+        };
+
+        unsafe {
+            ptr::write_bytes(pointer.add(layout.size()), CANARY, CANARY_SIZE);
+            ptr::write(
+                record,
+                RedZone {
+                    offset: pointer as usize - self.base.get() as usize,
+                    size: layout.size(),
+                    next: self.red_zones.get(),
+                    type_name,
+                },
+            );
+        }
+        self.red_zones.set(record);
+        Ok(pointer)
+    }
+
+    /// Get the record before `record` in the list of red zones. Records can
+    /// be overwritten by an overrun, so a link that doesn't point to an
+    /// earlier record ends the list.
+    #[cfg(feature = "red-zone")]
+    fn next_red_zone(&self, record: *mut RedZone) -> *mut RedZone {
+        let next = unsafe { ptr::addr_of!((*record).next).read() };
+        if next < record
+            && next as *mut u8 >= self.base.get()
+            && next.is_aligned()
+        {
+            next
+        } else {
+            ptr::null_mut()
+        }
+    }
+
+    /// Forget the red zones of allocations at or after `used`
+    #[cfg(feature = "red-zone")]
+    fn release_red_zones(&self, used: usize) {
+        let end = self.base.get().wrapping_add(used) as *mut RedZone;
+        let mut record = self.red_zones.get();
+        while !record.is_null() && record >= end {
+            record = self.next_red_zone(record);
+        }
+        self.red_zones.set(record);
+    }
+
+    /// The red zone record of the most recent allocation, if it was made with
+    /// red zones enabled
+    #[cfg(feature = "red-zone")]
+    fn last_red_zone(&self) -> Option<*mut RedZone> {
+        let record = self.red_zones.get();
+        if record.is_null() {
+            return None;
+        }
+        let (offset, size) = unsafe { ((*record).offset, (*record).size) };
+        let end = offset
+            .checked_add(size)
+            .and_then(|end| end.checked_add(CANARY_SIZE));
+        (end == Some(self.used.get())).then_some(record)
+    }
+
+    /// Set the number of bytes in use. Anything that gives memory back to the
+    /// arena goes through here, so the high water mark only needs to be
+    /// updated when `used` is about to go down, and handles to released values
+    /// are invalidated here. Released memory is poisoned for AddressSanitizer,
+    /// and memory taken by growing the last allocation is unpoisoned.
+    fn set_used(&self, used: usize) {
+        self.release_handles(used);
+        #[cfg(feature = "red-zone")]
+        self.release_red_zones(used);
+        #[cfg(feature = "stats")]
+        {
+            let mut stats = self.stats.get();
+            stats.high_water_mark = stats.high_water_mark.max(self.used.get());
+            self.stats.set(stats);
+        }
+        let old = self.used.get();
+        if used < old {
+            let released = unsafe { self.base.get().add(used) };
+            // Padding in the released memory is already poisoned
+            #[cfg(feature = "debug-poison")]
+            unsafe {
+                asan::unpoison(released, old - used);
+                poison::fill(released, old - used, poison::FREED);
+            }
+            asan::poison(released, old - used);
+        } else {
+            asan::unpoison(unsafe { self.base.get().add(old) }, used - old);
+        }
+        self.used.set(used);
+    }
+
+    /// Count an allocation in the stats. `padding` is the padding of the
+    /// arena before the allocation was made.
+    #[cfg(feature = "stats")]
+    fn record_alloc(&self, succeeded: bool, padding: usize) {
+        let mut stats = self.stats.get();
+        if succeeded {
+            stats.allocations += 1;
+            stats.padding += self.padding.get() - padding;
         } else {
-            Err(AllocError::AtCapacity)
+            stats.failed_allocations += 1;
         }
+        self.stats.set(stats);
+    }
+
+    /// Take back the stats of an allocation that was given back unused.
+    /// `before` and `padding` were read before the allocation was made.
+    #[cfg(feature = "stats")]
+    fn unrecord_alloc(&self, before: ArenaStats, padding: usize) {
+        let mut stats = self.stats.get();
+        stats.allocations -= 1;
+        stats.padding -= self.padding.get() - padding;
+        stats.high_water_mark = before.high_water_mark;
+        self.stats.set(stats);
     }
 
     /// Get a pointer to available memory and update the used attribute
     /// Takes a type as an argument instead of a layout
+    #[cfg_attr(feature = "track-callers", track_caller)]
     fn get_alloc_ptr<T>(&self) -> Result<*mut u8, AllocError> {
         let layout = Layout::new::<T>();
-        let pointer = self.get_alloc_ptr_with_layout(layout)?;
+        let pointer = self.get_alloc_ptr_with_layout::<T>(layout)?;
         Ok(pointer)
     }
 
+    /// Get the layout for an array of `count` elements of type `T`
+    fn array_layout<T>(count: usize) -> Result<Layout, AllocError> {
+        Layout::array::<T>(count).map_err(|_| AllocError::LayoutOverflow)
+    }
+
+    /// Check whether the `size` bytes at `pointer` are the most recent
+    /// allocation in the arena
+    fn is_last(&self, pointer: *const u8, size: usize) -> bool {
+        #[cfg(feature = "red-zone")]
+        if let Some(record) = self.last_red_zone() {
+            let (offset, last_size) =
+                unsafe { ((*record).offset, (*record).size) };
+            return size == last_size
+                && pointer == self.base.get().wrapping_add(offset);
+        }
+        let used = self.used.get();
+        size <= used && pointer == self.base.get().wrapping_add(used - size)
+    }
+
+    /// Change the size of the most recent allocation from `old_size` to
+    /// `new_size` bytes without moving it. Leaves the arena unchanged and
+    /// returns `AllocError::NotLast` if the allocation at `pointer` is not the
+    /// most recent one, or `AllocError::AtCapacity` with `align` if there is
+    /// not enough space left to grow it.
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn resize_last(
+        &self,
+        pointer: *const u8,
+        old_size: usize,
+        new_size: usize,
+        align: usize,
+    ) -> Result<(), AllocError> {
+        if !self.is_last(pointer, old_size) {
+            return Err(AllocError::NotLast);
+        }
+        // A red zone's canary moves with the end of its allocation
+        #[cfg(feature = "red-zone")]
+        let record = self.last_red_zone();
+        #[cfg(feature = "red-zone")]
+        let canary = record.map_or(0, |_| CANARY_SIZE);
+        #[cfg(not(feature = "red-zone"))]
+        let canary = 0;
+        let start = self.used.get() - canary - old_size;
+        if new_size > self.capacity - canary - start {
+            let error = AllocError::AtCapacity {
+                requested: new_size,
+                align,
+                remaining: self.capacity - canary - start,
+                top_callers: TopCallers::NONE,
+            };
+            #[cfg(feature = "track-callers")]
+            let error = self.with_top_callers(error);
+            return Err(error);
+        }
+        #[cfg(feature = "track-callers")]
+        let used = self.used.get();
+        self.set_used(start + new_size + canary);
+        // Growing counts for the caller, and shrinking gives the bytes back
+        // to it if it has made allocations
+        #[cfg(feature = "track-callers")]
+        {
+            let new_used = self.used.get();
+            let mut callers = self.callers.borrow_mut();
+            if new_used > used {
+                callers.record(self.caller(), new_used - used, 0);
+            } else {
+                callers.release(self.caller(), used - new_used, 0);
+            }
+        }
+        #[cfg(feature = "red-zone")]
+        if let Some(record) = record {
+            unsafe {
+                (*record).size = new_size;
+                let end = self.base.get().add(start + new_size);
+                ptr::write_bytes(end, CANARY, CANARY_SIZE);
+            }
+        }
+        Ok(())
+    }
+
     /// Allocate and initialize a single instance of a data structure.
     /// # Arguments
     /// * `val` - The value to initialize the instance to.
@@ -90,6 +709,8 @@ impl FixedArena {
     ///     Err(_) => assert!(false)
     /// };
     /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
     pub fn alloc<T>(&self, val: T) -> Result<&mut T, AllocError> {
         let pointer = self.get_alloc_ptr::<T>()?;
         unsafe {
@@ -99,6 +720,171 @@ impl FixedArena {
         }
     }
 
+    /// Allocate a single instance of a data structure and initialize it with
+    /// the value returned by `f`. The memory is reserved before `f` is called,
+    /// so the optimizer can build the value directly in the arena instead of
+    /// building it on the stack and copying it in, as `alloc` may do for
+    /// large types.
+    /// # Arguments
+    /// * `f` - Makes the value to initialize the instance to
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(1 << 16, 8);
+    /// let table = arena.alloc_with(|| [7_u64; 4096]).unwrap();
+    /// assert_eq!(table[4095], 7);
+    /// ```
+    #[inline(always)]
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_with<T, F>(&self, f: F) -> Result<&mut T, AllocError>
+    where
+        F: FnOnce() -> T,
+    {
+        let pointer = self.get_alloc_ptr::<T>()? as *mut T;
+        unsafe {
+            ptr::write(pointer, f());
+            Ok(&mut *pointer)
+        }
+    }
+
+    /// Allocate a single instance of a data structure and initialize it with
+    /// the value returned by `f`, like `alloc_with` does. If `f` returns an
+    /// error, it is returned as `AllocOrInitError::Init` and the reserved
+    /// memory is given back to the arena, along with its count in the stats
+    /// and call site totals. The memory is only given back when nothing else
+    /// was allocated from the arena while `f` ran, and otherwise still counts
+    /// as an allocation.
+    /// The arena is not used when the allocation fails, so `f` is not called
+    /// and `AllocOrInitError::Alloc` is returned.
+    /// # Arguments
+    /// * `f` - Makes the value to initialize the instance to, or an error
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{errors::AllocOrInitError, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let value = arena.try_alloc_with(|| "42".parse::<u32>()).unwrap();
+    /// assert_eq!(*value, 42);
+    /// match arena.try_alloc_with(|| "forty two".parse::<u32>()) {
+    ///     Ok(_) => assert!(false),
+    ///     Err(error) => assert!(matches!(error, AllocOrInitError::Init(_))),
+    /// };
+    /// ```
+    #[inline(always)]
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn try_alloc_with<T, E, F>(
+        &self,
+        f: F,
+    ) -> Result<&mut T, AllocOrInitError<E>>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        let used = self.used.get();
+        let padding = self.padding.get();
+        #[cfg(feature = "stats")]
+        let stats = self.stats.get();
+        let pointer = self.get_alloc_ptr::<T>()? as *mut T;
+        match f() {
+            Ok(val) => unsafe {
+                ptr::write(pointer, val);
+                Ok(&mut *pointer)
+            },
+            Err(error) => {
+                if self.is_last(pointer as *const u8, mem::size_of::<T>()) {
+                    #[cfg(feature = "track-callers")]
+                    self.callers.borrow_mut().release(
+                        self.caller(),
+                        self.used.get() - used,
+                        1,
+                    );
+                    self.set_used(used);
+                    #[cfg(feature = "stats")]
+                    self.unrecord_alloc(stats, padding);
+                    self.padding.set(padding);
+                }
+                Err(AllocOrInitError::Init(error))
+            }
+        }
+    }
+
+    /// Allocate and initialize a single instance of a data structure, and
+    /// register its destructor with the arena. Registered destructors are run
+    /// in the reverse order of allocation when the arena is reset or dropped.
+    /// Types that don't need to be dropped are allocated like `alloc` does.
+    /// Otherwise a small entry for the destructor is also stored in the arena.
+    /// `T` must be `Send`, because the arena can be sent to another thread
+    /// and the value is dropped wherever the arena is reset or dropped.
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 8);
+    /// let name = arena.alloc_with_drop(String::from("arena")).unwrap();
+    /// name.push_str(" string");
+    /// assert_eq!(name, "arena string");
+    /// // the string is dropped here
+    /// arena.reset();
+    /// ```
+    /// ```compile_fail
+    /// # use std::rc::Rc;
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// arena.alloc_with_drop(Rc::new(5)).unwrap();
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_with_drop<T>(&self, val: T) -> Result<&mut T, AllocError>
+    where
+        T: Send,
+    {
+        if !mem::needs_drop::<T>() {
+            return self.alloc(val);
+        }
+
+        // The value and its entry count as a single allocation
+        let used = self.used.get();
+        let padding = self.padding.get();
+        let reserved = self.bump::<T>(Layout::new::<T>()).and_then(|pointer| {
+            let entry = self.bump::<DropEntry>(Layout::new::<DropEntry>())?;
+            Ok((pointer, entry as *mut DropEntry))
+        });
+        #[cfg(feature = "stats")]
+        self.record_alloc(reserved.is_ok(), padding);
+        let (pointer, entry) = match reserved {
+            Ok(reserved) => reserved,
+            Err(error) => {
+                #[cfg(feature = "red-zone")]
+                self.release_red_zones(used);
+                let value = unsafe { self.base.get().add(used) };
+                asan::poison(value, self.used.get() - used);
+                self.used.set(used);
+                self.padding.set(padding);
+                #[cfg(feature = "track-callers")]
+                let error = self.with_top_callers(error);
+                return Err(error);
+            }
+        };
+        #[cfg(feature = "track-callers")]
+        self.record_caller(used);
+
+        unsafe {
+            let result = pointer as *mut T;
+            ptr::write(result, val);
+            ptr::write(
+                entry,
+                DropEntry {
+                    drop_fn: drop_value::<T>,
+                    value: pointer,
+                    next: self.drops.get(),
+                },
+            );
+            self.drops.set(entry);
+            Ok(&mut *result)
+        }
+    }
+
     /// Allocate and initialize a single instance of a data structure. It is
     /// initialized with a value of 0
     /// # Arguments
@@ -114,7 +900,12 @@ impl FixedArena {
     ///     Err(_) => assert!(false)
     /// };
     /// ```
-    pub fn alloc_zeroed<T>(&self) -> Result<&mut T, AllocError> {
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_zeroed<T>(&self) -> Result<&mut T, AllocError>
+    where
+        T: Zeroable,
+    {
         let pointer = self.get_alloc_ptr::<T>()?;
         unsafe {
             let result = pointer as *mut T;
@@ -125,8 +916,8 @@ impl FixedArena {
 
     /// Allocates an array of type T with count elements. The initial value of
     /// the elements in the array is val.
-    /// alloc_array performs a cast from usize to isize, and will panic if
-    /// the count value does not fit in isize
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
     /// # Arguments
     /// * `val` - the value to initialize the elements in the array to
     /// * `count` - the number of elements to allocate for the array
@@ -144,6 +935,8 @@ impl FixedArena {
     ///     Err(_) => assert!(false)
     /// };
     /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
     pub fn alloc_array<T>(
         &self,
         val: T,
@@ -152,18 +945,16 @@ impl FixedArena {
     where
         T: Clone,
     {
-        let layout =
-            Layout::array::<T>(count).expect("Bad count value for array");
-        let pointer = self.get_alloc_ptr_with_layout(layout)?;
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout::<[T]>(layout)?;
 
         let result: &mut [T];
         unsafe {
             let pointer = pointer as *mut T;
-            let isize_count = count as isize;
-            for index in 0..isize_count {
-                ptr::write(pointer.offset(index), val.clone());
+            for index in 0..count {
+                ptr::write(pointer.add(index), val.clone());
             }
-            result = slice::from_raw_parts_mut(pointer as *mut T, count);
+            result = slice::from_raw_parts_mut(pointer, count);
         }
 
         Ok(result)
@@ -171,8 +962,8 @@ impl FixedArena {
 
     /// Allocates an array of type `T` with count elements. The initial value of
     /// the elements in the array is 0.
-    /// alloc_zeroed_array performs a cast from `usize` to `isize`, and will
-    /// panic if the count value does not fit in `isize`
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
     /// # Arguments
     /// * `T` - Generic. The type to allocate
     /// * `count` - the number of elements to allocate for the array
@@ -190,13 +981,17 @@ impl FixedArena {
     ///     Err(_) => assert!(false)
     /// };
     /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
     pub fn alloc_zeroed_array<T>(
         &self,
         count: usize,
-    ) -> Result<&mut [T], AllocError> {
-        let layout =
-            Layout::array::<T>(count).expect("Bad count value for array");
-        let pointer = self.get_alloc_ptr_with_layout(layout)?;
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Zeroable,
+    {
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout::<[T]>(layout)?;
         unsafe {
             ptr::write_bytes(pointer as *mut T, 0, count);
             let result = slice::from_raw_parts_mut(pointer as *mut T, count);
@@ -204,664 +999,2782 @@ impl FixedArena {
         }
     }
 
+    /// Allocates a single instance of a data structure without initializing
+    /// it. The `uninit` module has helpers for initializing slices.
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let value = arena.alloc_uninit::<bool>().unwrap();
+    /// let value = value.write(true);
+    /// assert!(*value);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_uninit<T>(&self) -> Result<&mut MaybeUninit<T>, AllocError> {
+        let pointer = self.get_alloc_ptr::<T>()?;
+        #[cfg(feature = "debug-poison")]
+        unsafe {
+            poison::fill(pointer, mem::size_of::<T>(), poison::UNINIT)
+        };
+        unsafe { Ok(&mut *(pointer as *mut MaybeUninit<T>)) }
+    }
+
+    /// Allocates an array of type `T` with count elements without
+    /// initializing them. The `uninit` module has helpers for initializing
+    /// the slice and turning it into a `&mut [T]`.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate
+    /// * `count` - the number of elements to allocate for the array
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{uninit, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let values = arena.alloc_uninit_slice::<char>(3).unwrap();
+    /// let values = uninit::write_with(values, |index| (b'a' + index as u8) as char);
+    /// assert_eq!(values, ['a', 'b', 'c']);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_uninit_slice<T>(
+        &self,
+        count: usize,
+    ) -> Result<&mut [MaybeUninit<T>], AllocError> {
+        let layout = FixedArena::array_layout::<T>(count)?;
+        let pointer = self.get_alloc_ptr_with_layout::<[T]>(layout)?;
+        #[cfg(feature = "debug-poison")]
+        unsafe {
+            poison::fill(pointer, layout.size(), poison::UNINIT)
+        };
+        unsafe {
+            let pointer = pointer as *mut MaybeUninit<T>;
+            Ok(slice::from_raw_parts_mut(pointer, count))
+        }
+    }
+
     /// Allocates an array of type `T` with count elements. The value of
     /// the elements in the array is uninitialized.
-    /// alloc_uninitialized_array performs a cast from usize to isize, and will
-    /// panic if the count value does not fit in isize
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// Prefer `alloc_uninit_slice`, which can be initialized without unsafe
+    /// code.
+    /// With the `debug-poison` feature, the memory is filled with
+    /// `poison::UNINIT`.
     /// # Arguments
     /// * `T` - Generic. The type to allocate
     /// * `count` - the number of elements to allocate for the array
+    /// # Safety
+    /// The returned elements are not valid values of `T`. Each element must
+    /// be written, for example with `ptr::write`, before it is read, dropped
+    /// or used through a reference.
     /// # Examples
     /// ```
     /// # use tea_fixed_arena::FixedArena;
     /// let arena = FixedArena::with_capacity(4096, 4);
-    /// match arena.alloc_uninitialized_array::<i32>(5){
+    /// let result = unsafe { arena.alloc_uninitialized_array::<i32>(5) };
+    /// match result {
     ///     Ok(result) => {
     ///         assert_eq!(result.len(), 5);
     ///     },
     ///     Err(_) => assert!(false)
     /// };
     /// ```
-    pub fn alloc_uninitialized_array<T>(
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub unsafe fn alloc_uninitialized_array<T>(
         &self,
         count: usize,
     ) -> Result<&mut [T], AllocError> {
-        let layout =
-            Layout::array::<T>(count).expect("Bad count value for array");
-        let pointer = self.get_alloc_ptr_with_layout(layout)?;
-        unsafe {
-            let result = slice::from_raw_parts_mut(pointer as *mut T, count);
-            Ok(result)
-        }
+        let result = self.alloc_uninit_slice::<T>(count)?;
+        unsafe { Ok(uninit::assume_init_slice_mut(result)) }
     }
 
-    /// Resets the arena. The `used` value is set to 0, and any data allocated
-    /// since the last reset cannot be used
-    /// Because the alloc method immutably borrows self and reset mutably
-    /// borrows self, a call to reset will invalidate all previous values that
-    /// were allocated using the alloc method. This is because Rust will not
-    /// allow an immutable borrow of self to exist past a mutable borrow of
-    /// self.
+    /// Allocates a copy of a string slice
+    /// # Arguments
+    /// * `val` - the string to copy into the arena
     /// # Examples
     /// ```
     /// # use tea_fixed_arena::FixedArena;
-    /// let mut arena = FixedArena::with_capacity(4096, 4);
-    /// let my_data = arena.alloc_zeroed_array::<i32>(32);
-    /// arena.reset();
-    /// // cannot use my_data after this point
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_str("arena").unwrap();
+    /// result.make_ascii_uppercase();
+    /// assert_eq!(result, "ARENA");
     /// ```
-    pub fn reset(&mut self) {
-        self.used.set(0);
-    }
-}
-
-impl Drop for FixedArena {
-    // TODO: document me
-    fn drop(&mut self) {
-        // TODO: remove magic alignment
-        let layout = Layout::from_size_align(self.capacity, self.base_align)
-            .expect("Layout failed");
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_str(&self, val: &str) -> Result<&mut str, AllocError> {
+        let layout = FixedArena::array_layout::<u8>(val.len())?;
+        let pointer = self.get_alloc_ptr_with_layout::<str>(layout)?;
         unsafe {
-            dealloc(self.base, layout);
+            ptr::copy_nonoverlapping(val.as_ptr(), pointer, val.len());
+            let bytes = slice::from_raw_parts_mut(pointer, val.len());
+            Ok(core::str::from_utf8_unchecked_mut(bytes))
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::{cmp::PartialEq, mem::size_of};
-    const DEFAULT_ALIGN: usize = 4;
+    /// Allocates a copy of a slice of `Copy` values
+    /// Returns `AllocError::LayoutOverflow` if the size of the slice does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `values` - the values to copy into the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_slice_copy(&[1, 2, 3]).unwrap();
+    /// result[0] = 4;
+    /// assert_eq!(result, [4, 2, 3]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_slice_copy<T>(
+        &self,
+        values: &[T],
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Copy,
+    {
+        let result = self.alloc_uninit_slice(values.len())?;
+        Ok(uninit::write_copy_of_slice(result, values))
+    }
 
-    use crate::test_common::{
-        I32Struct, LargerStruct, MixedStruct, SmallStruct, SmallerStruct,
-        TestStruct,
-    };
+    /// Allocates a slice with a clone of each of the values in `values`
+    /// Returns `AllocError::LayoutOverflow` if the size of the slice does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `values` - the values to clone into the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let names = [String::from("a"), String::from("b")];
+    /// let result = arena.alloc_slice_clone(&names).unwrap();
+    /// assert_eq!(result, names);
+    /// # for name in result.iter_mut() {
+    /// #     unsafe { core::ptr::drop_in_place(name) };
+    /// # }
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_slice_clone<T>(
+        &self,
+        values: &[T],
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Clone,
+    {
+        let result = self.alloc_uninit_slice(values.len())?;
+        Ok(uninit::write_clone_of_slice(result, values))
+    }
 
-    mod reset {
-        use super::*;
+    /// Allocates an array of type `T` with count elements. Each element is
+    /// initialized to the value `f` returns for its index.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `count` - the number of elements to allocate for the array
+    /// * `f` - called with the index of each element, in order, to make its
+    ///   value
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let result = arena.alloc_slice_fill_with(4, |index| index * 2).unwrap();
+    /// assert_eq!(result, [0, 2, 4, 6]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_slice_fill_with<T, F>(
+        &self,
+        count: usize,
+        f: F,
+    ) -> Result<&mut [T], AllocError>
+    where
+        F: FnMut(usize) -> T,
+    {
+        let result = self.alloc_uninit_slice(count)?;
+        Ok(uninit::write_with(result, f))
+    }
+
+    /// Allocates a slice with the values produced by an iterator
+    /// An iterator that reports its exact length through `size_hint` is
+    /// written into a single allocation of that length. Otherwise the slice
+    /// grows in place while it is the most recent allocation, and moves to a
+    /// larger allocation when it is not. Unused space at the end is given
+    /// back to the arena when the slice is still the most recent allocation.
+    /// If the arena runs out of space, the values produced so far are dropped
+    /// and the error is returned.
+    /// # Arguments
+    /// * `iter` - the values to move into the arena
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// let squares = arena.alloc_from_iter((1..4).map(|i| i * i)).unwrap();
+    /// assert_eq!(squares, [1, 4, 9]);
+    /// let even = arena
+    ///     .alloc_from_iter((0..10).filter(|i| i % 2 == 0))
+    ///     .unwrap();
+    /// assert_eq!(even, [0, 2, 4, 6, 8]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_from_iter<T, I>(&self, iter: I) -> Result<&mut [T], AllocError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let iter = iter.into_iter();
+        let mut values = ArenaVec::new_in(self);
+        if let (min, Some(max)) = iter.size_hint() {
+            if min == max {
+                values.reserve_exact(min)?;
+            }
+        }
+        values.extend(iter)?;
+
+        let capacity = values.capacity();
+        let result = values.into_slice();
+        if mem::size_of::<T>() != 0 && result.len() < capacity {
+            let _ = self.resize_last(
+                result.as_ptr() as *const u8,
+                capacity * mem::size_of::<T>(),
+                mem::size_of_val(result),
+                mem::align_of::<T>(),
+            );
+        }
+        Ok(result)
+    }
+
+    /// Grow the most recent allocation in place, from `old_size` to `new_size`
+    /// bytes. The new bytes are uninitialized.
+    /// Returns `AllocError::NotLast` if the `old_size` bytes at `pointer` are
+    /// not the most recent allocation, and `AllocError::AtCapacity` if there
+    /// is not enough space left. The arena is unchanged when an error is
+    /// returned.
+    /// Panics if `new_size` is smaller than `old_size`.
+    /// # Arguments
+    /// * `pointer` - The start of the most recent allocation
+    /// * `old_size` - The current size of the allocation in bytes
+    /// * `new_size` - The size to grow the allocation to in bytes
+    /// * `align` - The alignment of the allocation, which is reported in
+    ///   `AllocError::AtCapacity`
+    /// # Examples
+    /// ```
+    /// # use core::ptr::NonNull;
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(64, 4);
+    /// let values = arena.alloc_array(1_u32, 4).unwrap();
+    /// let pointer = NonNull::from(values).cast::<u8>();
+    /// arena.try_grow_last(pointer, 16, 32, 4).unwrap();
+    /// assert!(arena.try_grow_last(pointer, 32, 128, 4).is_err());
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn try_grow_last(
+        &self,
+        pointer: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+        align: usize,
+    ) -> Result<(), AllocError> {
+        assert!(new_size >= old_size, "new_size is smaller than old_size");
+        self.resize_last(pointer.as_ptr(), old_size, new_size, align)
+    }
+
+    /// Shrink the most recent allocation in place, from `old_size` to
+    /// `new_size` bytes, and give the bytes after it back to the arena.
+    /// Returns `AllocError::NotLast`, leaving the arena unchanged, if the
+    /// `old_size` bytes at `pointer` are not the most recent allocation.
+    /// Panics if `new_size` is larger than `old_size`. Handles from
+    /// `alloc_handle` to the bytes given back stop working, like after a
+    /// `rollback`.
+    /// # Arguments
+    /// * `pointer` - The start of the most recent allocation
+    /// * `old_size` - The current size of the allocation in bytes
+    /// * `new_size` - The size to shrink the allocation to in bytes
+    /// # Safety
+    /// Nothing may use the bytes after the first `new_size` bytes of the
+    /// allocation after this call, since they can be handed out again.
+    /// # Examples
+    /// ```
+    /// # use core::ptr::NonNull;
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(64, 4);
+    /// let values = arena.alloc_array(1_u32, 16).unwrap();
+    /// let pointer = NonNull::from(values).cast::<u8>();
+    /// let values = unsafe {
+    ///     arena.shrink_last(pointer, 64, 8).unwrap();
+    ///     std::slice::from_raw_parts_mut(pointer.cast::<u32>().as_ptr(), 2)
+    /// };
+    /// arena.alloc_array(2_u32, 14).unwrap();
+    /// assert_eq!(values, [1, 1]);
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub unsafe fn shrink_last(
+        &self,
+        pointer: NonNull<u8>,
+        old_size: usize,
+        new_size: usize,
+    ) -> Result<(), AllocError> {
+        assert!(new_size <= old_size, "new_size is larger than old_size");
+        // Shrinking can't run out of space, so the alignment is never reported
+        self.resize_last(pointer.as_ptr(), old_size, new_size, 1)
+    }
+
+    /// Free the most recent allocation and give its bytes back to the arena.
+    /// Returns `AllocError::NotLast`, leaving the arena unchanged, if the
+    /// `size` bytes at `pointer` are not the most recent allocation.
+    /// Padding that was added before the allocation is not given back.
+    /// Handles from `alloc_handle` to the allocation stop working, like after
+    /// a `rollback`.
+    /// # Arguments
+    /// * `pointer` - The start of the most recent allocation
+    /// * `size` - The size of the allocation in bytes
+    /// # Safety
+    /// Nothing may use the allocation after this call, since its memory can
+    /// be handed out again. Destructors registered for it with
+    /// `alloc_with_drop` are not run or unregistered, so it must not be such
+    /// an allocation.
+    /// # Examples
+    /// ```
+    /// # use core::ptr::NonNull;
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(8, 4);
+    /// let value = arena.alloc(1_u64).unwrap();
+    /// let pointer = NonNull::from(value).cast::<u8>();
+    /// unsafe { arena.pop_last(pointer, 8).unwrap() };
+    /// arena.alloc(2_u64).unwrap();
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub unsafe fn pop_last(
+        &self,
+        pointer: NonNull<u8>,
+        size: usize,
+    ) -> Result<(), AllocError> {
+        self.resize_last(pointer.as_ptr(), size, 0, 1)
+    }
+
+    /// Allocate and initialize a single instance of a data structure, and
+    /// return a handle to it instead of a reference. The handle does not
+    /// borrow the arena. Use `get` and `get_mut` to reach the value while the
+    /// arena has not been reset or rolled back past it. The value's destructor
+    /// is registered like `alloc_with_drop` does.
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// let handle = arena.alloc_handle(String::from("name")).unwrap();
+    /// arena.get_mut(handle).unwrap().push_str("d");
+    /// assert_eq!(arena.get(handle).unwrap(), "named");
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_handle<T>(&self, val: T) -> Result<ArenaHandle<T>, AllocError>
+    where
+        T: Send,
+    {
+        let pointer = self.alloc_with_drop(val)? as *mut T as *mut u8;
+        self.handles_end.set(self.used.get());
+        let offset = pointer as usize - self.base.get() as usize;
+        Ok(ArenaHandle::new(offset, self.generation.get()))
+    }
+
+    /// Get a reference to the value behind a handle from `alloc_handle`.
+    /// Returns `None` if the handle was made by another arena, or before the
+    /// arena was reset, rolled back, shrunk or popped past the value.
+    /// # Arguments
+    /// * `handle` - The handle to the value
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// let handle = arena.alloc_handle(5).unwrap();
+    /// assert_eq!(arena.get(handle), Some(&5));
+    /// arena.reset();
+    /// assert_eq!(arena.get(handle), None);
+    /// ```
+    pub fn get<T>(&self, handle: ArenaHandle<T>) -> Option<&T> {
+        let pointer = self.handle_ptr(handle)?;
+        unsafe { Some(&*pointer) }
+    }
+
+    /// Get a mutable reference to the value behind a handle from
+    /// `alloc_handle`. Returns `None` if the handle was made by another arena,
+    /// or before the arena was reset, rolled back, shrunk or popped past the
+    /// value.
+    /// This mutably borrows the arena, since several handles may refer to the
+    /// same value.
+    /// # Arguments
+    /// * `handle` - The handle to the value
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// let handle = arena.alloc_handle(5).unwrap();
+    /// *arena.get_mut(handle).unwrap() += 1;
+    /// assert_eq!(arena.get(handle), Some(&6));
+    /// ```
+    pub fn get_mut<T>(&mut self, handle: ArenaHandle<T>) -> Option<&mut T> {
+        let pointer = self.handle_ptr(handle)?;
+        unsafe { Some(&mut *pointer) }
+    }
+
+    /// Get a pointer to the value behind a handle, if the handle belongs to
+    /// the current generation of this arena
+    fn handle_ptr<T>(&self, handle: ArenaHandle<T>) -> Option<*mut T> {
+        if handle.generation != self.generation.get()
+            || handle.offset + mem::size_of::<T>() > self.used.get()
+        {
+            return None;
+        }
+        Some(self.base.get().wrapping_add(handle.offset) as *mut T)
+    }
+
+    /// Move the arena to a new generation if memory at or above the offset
+    /// `used` is about to be released and holds a value from `alloc_handle`.
+    /// Handles to values below `used` are also invalidated in that case,
+    /// since a generation applies to the whole arena.
+    fn release_handles(&self, used: usize) {
+        if self.handles_end.get() > used {
+            self.generation.set(next_generation());
+            self.handles_end.set(0);
+        }
+    }
+
+    /// Resets the arena. The `used` value is set to 0, and any data allocated
+    /// since the last reset cannot be used
+    /// With the `debug-poison` feature, the released memory is filled with
+    /// `poison::FREED`.
+    /// Because the alloc method immutably borrows self and reset mutably
+    /// borrows self, a call to reset will invalidate all previous values that
+    /// were allocated using the alloc method. This is because Rust will not
+    /// allow an immutable borrow of self to exist past a mutable borrow of
+    /// self.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// let my_data = arena.alloc_zeroed_array::<i32>(32);
+    /// arena.reset();
+    /// // cannot use my_data after this point
+    /// ```
+    pub fn reset(&mut self) {
+        self.run_drops(0);
+        self.set_used(0);
+        self.padding.set(0);
+        self.epoch.set(next_generation());
+        #[cfg(feature = "track-callers")]
+        {
+            *self.callers.get_mut() = CallerReport::new();
+        }
+        #[cfg(feature = "stats")]
+        {
+            let mut stats = self.stats.get();
+            stats.resets += 1;
+            self.stats.set(stats);
+        }
+    }
+
+    /// Mark the current point in the arena so that it can be returned to
+    /// with `rollback`.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// arena.alloc(1).unwrap();
+    /// let checkpoint = arena.checkpoint();
+    /// arena.alloc(2).unwrap();
+    /// arena.rollback(checkpoint);
+    /// assert_eq!(arena.checkpoint(), checkpoint);
+    /// ```
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            epoch: self.epoch.get(),
+            used: self.used.get(),
+            padding: self.padding.get(),
+        }
+    }
+
+    /// Release everything that was allocated after a checkpoint was made, and
+    /// run the destructors of values allocated with `alloc_with_drop` since
+    /// then. Like `reset`, this mutably borrows self, so no allocation made
+    /// before the rollback can be used after it.
+    /// Does nothing if the checkpoint was made by another arena or before the
+    /// arena was last reset, or if the arena is already at or before the
+    /// checkpoint. A checkpoint that the arena was rolled back past and has
+    /// since allocated beyond again still releases what is above it, but its
+    /// padding is no longer known, so `padding` is only lowered to what it was
+    /// at the checkpoint.
+    /// # Arguments
+    /// * `checkpoint` - The point to return to, from `checkpoint`
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// arena.alloc(1).unwrap();
+    /// let checkpoint = arena.checkpoint();
+    /// arena.alloc_zeroed_array::<i32>(1023).unwrap();
+    /// arena.rollback(checkpoint);
+    /// arena.alloc_zeroed_array::<i32>(1023).unwrap();
+    /// ```
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        if checkpoint.epoch != self.epoch.get()
+            || checkpoint.used > self.used.get()
+        {
+            return;
+        }
+        self.run_drops(checkpoint.used);
+        self.set_used(checkpoint.used);
+        // The padding only grows between a checkpoint and later allocations,
+        // unless the arena went back past the checkpoint in between
+        self.padding.set(self.padding.get().min(checkpoint.padding));
+    }
+
+    /// Make a scope that rolls the arena back to its current point when the
+    /// scope is dropped. See `ArenaScope`.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// {
+    ///     let scope = arena.scope();
+    ///     scope.alloc(1).unwrap();
+    /// }
+    /// ```
+    pub fn scope(&mut self) -> ArenaScope<'_, 'a> {
+        ArenaScope::new(self)
+    }
+
+    /// Run the destructors registered with `alloc_with_drop` for values that
+    /// are stored at or above the offset `used`, from the most recent to the
+    /// oldest. Entries are always added above the previous entry, so the list
+    /// is sorted by address. Each entry is unlinked before its destructor
+    /// runs, so a panicking destructor can't cause a value to be dropped twice
+    fn run_drops(&self, used: usize) {
+        let end = self.base.get().wrapping_add(used) as *mut DropEntry;
+        loop {
+            let entry = self.drops.get();
+            if entry.is_null() || entry < end {
+                break;
+            }
+            unsafe {
+                let DropEntry {
+                    drop_fn,
+                    value,
+                    next,
+                } = ptr::read(entry);
+                self.drops.set(next);
+                drop_fn(value);
+            }
+        }
+    }
+
+    /// The number of bytes that have been skipped to satisfy the alignment of
+    /// allocations since the last reset. These bytes are included in the
+    /// amount of memory used by the arena.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// arena.alloc(1u8).unwrap();
+    /// arena.alloc(1u64).unwrap();
+    /// assert_eq!(arena.padding(), 7);
+    /// ```
+    pub fn padding(&self) -> usize {
+        self.padding.get()
+    }
+
+    /// The number of bytes in use since the last reset, including padding
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// arena.alloc(1_u32).unwrap();
+    /// assert_eq!(arena.used(), 4);
+    /// ```
+    pub fn used(&self) -> usize {
+        self.used.get()
+    }
+
+    /// The number of bytes that have not been used yet. An allocation of
+    /// this size can still fail if it needs padding for its alignment.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// arena.alloc(1_u32).unwrap();
+    /// assert_eq!(arena.remaining(), 4092);
+    /// ```
+    pub fn remaining(&self) -> usize {
+        self.capacity - self.used.get()
+    }
+
+    /// The total number of bytes the arena can allocate
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(4096, 4);
+    /// assert_eq!(arena.capacity(), 4096);
+    /// ```
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Usage statistics of the arena since it was made. Needs the `stats`
+    /// feature.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(64, 8);
+    /// arena.alloc_array(0_u8, 48).unwrap();
+    /// arena.reset();
+    /// arena.alloc(1_u8).unwrap();
+    /// arena.alloc(2_u64).unwrap();
+    /// assert!(arena.alloc_array(0_u8, 64).is_err());
+    ///
+    /// let stats = arena.stats();
+    /// assert_eq!(stats.high_water_mark, 48);
+    /// assert_eq!(stats.allocations, 3);
+    /// assert_eq!(stats.failed_allocations, 1);
+    /// assert_eq!(stats.padding, 7);
+    /// assert_eq!(stats.resets, 1);
+    /// ```
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> ArenaStats {
+        let mut stats = self.stats.get();
+        stats.high_water_mark = stats.high_water_mark.max(self.used.get());
+        stats
+    }
+
+    /// The call sites that allocated from the arena since it was last reset,
+    /// with the bytes and number of allocations of each, from the most bytes
+    /// to the fewest. Allocation methods are `#[track_caller]`, so the call
+    /// sites are in the code that uses the arena. Needs the `track-callers`
+    /// feature.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let arena = FixedArena::with_capacity(64, 8);
+    /// for _ in 0..4 {
+    ///     arena.alloc(0_u64).unwrap();
+    /// }
+    /// if let Err(error) = arena.alloc_array(0_u64, 8) {
+    ///     // Lists the call site of `arena.alloc` with 32 bytes
+    ///     println!("{}", error);
+    /// }
+    /// let report = arena.report();
+    /// let site = report.iter().next().unwrap();
+    /// assert_eq!((site.bytes, site.allocations), (32, 4));
+    /// print!("{}", report);
+    /// ```
+    #[cfg(feature = "track-callers")]
+    pub fn report(&self) -> CallerReport {
+        let mut report = *self.callers.borrow();
+        report.sort();
+        report
+    }
+
+    /// Turn red zones on or off for later allocations. With red zones, every
+    /// allocation is followed by `red_zone::CANARY_SIZE` canary bytes and
+    /// preceded by a record of its offset, size and type, which `verify`
+    /// uses to find allocations that were written past their end.
+    /// Allocations that were already made keep or lack their red zone.
+    /// Needs the `red-zone` feature.
+    /// # Arguments
+    /// * `enabled` - Whether later allocations get a red zone
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 8);
+    /// arena.set_red_zones(true);
+    /// arena.alloc(1_u64).unwrap();
+    /// assert!(arena.used() > 8);
+    /// ```
+    #[cfg(feature = "red-zone")]
+    pub fn set_red_zones(&mut self, enabled: bool) {
+        self.red_zones_enabled = enabled;
+    }
+
+    /// Check the canaries of all allocations made with red zones, and return
+    /// the first allocation in the arena whose canary was overwritten.
+    /// An overrun that goes past the canary writes over the record of the
+    /// next allocation. The record is then reported instead, with the type
+    /// name of the arena's internal `RedZone` record, since the allocations
+    /// before it can't be found anymore. Needs the `red-zone` feature.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 8);
+    /// arena.set_red_zones(true);
+    /// let values = arena.alloc_array(0_u32, 4).unwrap();
+    /// values[3] = 5;
+    /// match arena.verify() {
+    ///     Ok(()) => {}
+    ///     Err(overrun) => panic!("{}", overrun),
+    /// }
+    /// ```
+    #[cfg(feature = "red-zone")]
+    pub fn verify(&self) -> Result<(), Overrun> {
+        let mut overrun = Ok(());
+        // Allocations must end before the record after them
+        let mut limit = self.used.get();
+        let mut record = self.red_zones.get();
+        while !record.is_null() {
+            let (offset, size) = unsafe { ((*record).offset, (*record).size) };
+            let record_offset = record as usize - self.base.get() as usize;
+            let in_bounds = offset >= record_offset + mem::size_of::<RedZone>()
+                && size <= limit
+                && offset <= limit - size
+                && CANARY_SIZE <= limit - size - offset;
+            if !in_bounds {
+                // The record itself was written over by an overrun of an
+                // earlier allocation, which can't be found without it
+                return Err(Overrun {
+                    offset: record_offset,
+                    size: mem::size_of::<RedZone>(),
+                    type_name: core::any::type_name::<RedZone>(),
+                });
+            }
+
+            let canary = unsafe {
+                slice::from_raw_parts(
+                    self.base.get().add(offset + size),
+                    CANARY_SIZE,
+                )
+            };
+            if canary.iter().any(|&byte| byte != CANARY) {
+                let type_name = unsafe { (*record).type_name };
+                overrun = Err(Overrun {
+                    offset,
+                    size,
+                    type_name,
+                });
+            }
+            limit = record_offset;
+            record = self.next_red_zone(record);
+        }
+        overrun
+    }
+}
+
+impl Drop for FixedArena<'_> {
+    /// Runs the destructors registered with `alloc_with_drop` and frees the
+    /// memory of the arena if it was allocated by the arena
+    fn drop(&mut self) {
+        self.run_drops(0);
+        // The memory goes back to its owner, which may use all of it. An
+        // unplaced arena that was never used has no memory.
+        if !self.base.get().is_null() {
+            asan::unpoison(self.base.get(), self.capacity);
+        }
+        #[cfg(any(feature = "std", test))]
+        if let Some(layout) = self.owned {
+            unsafe {
+                dealloc(self.base.get(), layout);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+// The original tests predate running clippy on the test code
+#[allow(
+    unused_mut,
+    clippy::assertions_on_constants,
+    clippy::excessive_precision,
+    clippy::needless_borrow,
+    clippy::neg_multiply
+)]
+mod tests {
+    use super::*;
+    use std::{
+        cmp::PartialEq,
+        mem::{align_of, size_of},
+    };
+    const DEFAULT_ALIGN: usize = 4;
+
+    use crate::test_common::{
+        I32Struct, LargerStruct, MixedStruct, OverAlignedStruct, SmallStruct,
+        SmallerStruct, TestStruct, ThreeByteStruct,
+    };
+
+    mod create {
+        use super::*;
+
+        /// Test creating an arena with the fallible constructor
+        #[test]
+        fn try_with_capacity() {
+            let arena =
+                FixedArena::try_with_capacity(1024, DEFAULT_ALIGN).unwrap();
+            let test = arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+            assert!(test.x == 1.0);
+            assert!(test.y == 2.0);
+        }
+
+        /// Test that alignments that aren't powers of two are rejected
+        #[test]
+        fn invalid_alignment() {
+            for align in [0, 3, 12] {
+                match FixedArena::try_with_capacity(1024, align) {
+                    Ok(_) => panic!("creation should fail"),
+                    Err(err) => assert_eq!(err, AllocError::InvalidAlignment),
+                };
+            }
+        }
+
+        /// Test that a capacity of 0 is rejected
+        #[test]
+        fn zero_capacity() {
+            match FixedArena::try_with_capacity(0, DEFAULT_ALIGN) {
+                Ok(_) => panic!("creation should fail"),
+                Err(err) => assert_eq!(err, AllocError::ZeroCapacity),
+            };
+        }
+
+        /// Test that capacities that don't fit in an isize are rejected
+        #[test]
+        fn capacity_overflow() {
+            match FixedArena::try_with_capacity(usize::MAX - 1, DEFAULT_ALIGN) {
+                Ok(_) => panic!("creation should fail"),
+                Err(err) => assert_eq!(err, AllocError::CapacityOverflow),
+            };
+        }
+
+        /// Test that a failure in the system allocator is reported. Miri
+        /// stops the program instead of failing the allocation.
+        #[test]
+        #[cfg_attr(miri, ignore)]
+        fn out_of_memory() {
+            match FixedArena::try_with_capacity(isize::MAX as usize, 1) {
+                Ok(_) => panic!("creation should fail"),
+                Err(err) => assert_eq!(err, AllocError::OutOfMemory),
+            };
+        }
+
+        /// Test that the panicking constructor panics on bad arguments
+        #[test]
+        #[should_panic]
+        fn with_capacity_panics() {
+            FixedArena::with_capacity(0, DEFAULT_ALIGN);
+        }
+    }
+
+    mod from_buffer {
+        use super::*;
+
+        /// Test allocating from a buffer provided by the caller
+        #[test]
+        fn alloc() {
+            let mut buffer = [MaybeUninit::uninit(); 256];
+            let arena = FixedArena::from_buffer(&mut buffer);
+            let test = arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+            let array =
+                arena.alloc_array(I32Struct { x: 1, y: -1 }, 8).unwrap();
+            assert!(test.x == 1.0);
+            assert!(test.y == 2.0);
+            assert_eq!(array[7], I32Struct { x: 1, y: -1 });
+        }
+
+        /// Test that allocations are aligned in a buffer that isn't
+        #[test]
+        fn unaligned_buffer() {
+            let mut buffer = [MaybeUninit::uninit(); 257];
+            let start = buffer.as_ptr().align_offset(8) + 1;
+            let arena = FixedArena::from_buffer(&mut buffer[start..]);
+            let value = arena.alloc(LargerStruct { x: 1, y: 2 }).unwrap();
+            assert!((value as *const LargerStruct).is_aligned());
+            assert_eq!(arena.padding(), 7);
+        }
+
+        /// Test filling a buffer to capacity
+        #[test]
+        fn over_capacity() {
+            let mut buffer = [MaybeUninit::uninit(); 64];
+            let mut arena = FixedArena::from_buffer(&mut buffer);
+            arena.alloc_zeroed_array::<u8>(64).unwrap();
+            match arena.alloc(1_u8) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+            arena.reset();
+            arena.alloc_zeroed_array::<u8>(64).unwrap();
+        }
+
+        /// Test that an empty buffer only fails allocations
+        #[test]
+        fn empty_buffer() {
+            let mut buffer = [];
+            let arena = FixedArena::from_buffer(&mut buffer);
+            assert!(arena.alloc(1_u8).is_err());
+        }
+
+        /// Test that dropping the arena leaves the buffer alone, and runs
+        /// registered destructors
+        #[test]
+        fn drop_arena() {
+            let mut buffer = [MaybeUninit::uninit(); 64];
+            let text = String::from("dropped");
+            {
+                let arena = FixedArena::from_buffer(&mut buffer);
+                arena.alloc(0xAB_u8).unwrap();
+                arena.alloc_with_drop(text).unwrap();
+            }
+            assert_eq!(unsafe { buffer[0].assume_init() }, 0xAB);
+        }
+    }
+
+    mod reset {
+        use super::*;
+
+        /// Test resetting the arena
+        #[test]
+        fn reset() {
+            let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            {
+                arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
+            }
+            arena.reset();
+            assert!(arena.used.get() == 0);
+            {
+                let test_two =
+                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+
+                assert!(test_two.x == 1.0);
+                assert!(test_two.y == 2.0);
+            }
+        }
+
+        /// Test resetting the arena after allocating and mutating mutable data
+        #[test]
+        fn reset_with_mut() {
+            let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+
+            {
+                let mut test =
+                    arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
+                assert!(test.x == 0.0);
+                test.x = 1.0;
+                assert!(test.x == 1.0);
+            }
+
+            arena.reset();
+            assert!(arena.used.get() == 0);
+
+            {
+                let test_two =
+                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+
+                assert!(test_two.x == 1.0);
+                assert!(test_two.y == 2.0);
+            }
+        }
+
+        /// Test resetting multiple times in the same loop
+        #[test]
+        fn reset_in_loop() {
+            let capacity = 1024;
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            for index in 0..capacity {
+                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+                arena.reset();
+            }
+        }
+    }
+
+    mod alloc_struct {
+        use super::*;
+
+        /// Test allocating a single struct
+        #[test]
+        fn basic_allocation() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            {
+                let test = arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
+                assert!(test.x == 0.0);
+                assert!(test.y == 0.0);
+            }
+        }
+
+        /// Test allocating a zeroed single struct
+        #[test]
+        fn alloc_zeroed() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            {
+                let test = arena.alloc_zeroed::<TestStruct>().unwrap();
+                assert!(test.x == 0.0);
+                assert!(test.y == 0.0);
+            }
+        }
+
+        /// Test allocating multiple structurese with the alloc call
+        #[test]
+        fn multiple_allocation() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+
+            {
+                let test = arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
+                let test_two =
+                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+
+                assert!(test.x == 0.0);
+                assert!(test.y == 0.0);
+                assert!(test_two.x == 1.0);
+                assert!(test_two.y == 2.0);
+            }
+        }
+
+        /// Test allocating multiple different structures with different
+        /// alignments
+        #[test]
+        fn mixed_allocation() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+
+            let first = TestStruct { x: 1.0, y: -1.0 };
+            let first_result = alloc_and_check(&arena, first);
+
+            let second = LargerStruct {
+                x: (1 << 42),
+                y: -1 * (1 << 42),
+            };
+            let second_result = alloc_and_check(&arena, second);
+
+            let third = SmallerStruct {
+                x: 1 << 9,
+                y: -1 * (1 << 9),
+            };
+            let third_result = alloc_and_check(&arena, third);
+
+            let fourth = SmallStruct { x: 127, y: -1 };
+            let fourth_result = alloc_and_check(&arena, fourth);
+
+            let fifth = MixedStruct {
+                a: 1 << 33,
+                b: 1 << 17,
+                c: 1 << 9,
+                d: 127,
+                e: 1.000454846,
+                f: -1.000454846,
+                g: 0xFFFF,
+                h: 0xFF,
+            };
+            let fifth_result = alloc_and_check(&arena, fifth);
+
+            assert!(first == *first_result);
+            assert!(second == *second_result);
+            assert!(third == *third_result);
+            assert!(fourth == *fourth_result);
+            assert!(fifth == *fifth_result);
+        }
+
+        /// Test filling up the arena to capacity
+        #[test]
+        fn at_capacity() {
+            let capacity = 1024;
+            let count = capacity / size_of::<TestStruct>();
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            for index in 0..count {
+                let mut test =
+                    arena.alloc(TestStruct { x: 1.0, y: -1.0 }).unwrap();
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+        }
+
+        /// Test resetting the arena after filling it up to capacity
+        /// Should be able to continue allocating
+        #[test]
+        fn reset_at_capacity() {
+            let capacity = 1024;
+            let count = capacity;
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            for index in 0..count {
+                let test = match arena.alloc(TestStruct { x: 1.0, y: -1.0 }) {
+                    Ok(result) => result,
+                    Err(error) => match error {
+                        // if we're at capacity, reset and retry, panic if alloc
+                        // fails again
+                        AllocError::AtCapacity { .. } => {
+                            arena.reset();
+                            arena.alloc(TestStruct { x: 1.0, y: -1.0 }).unwrap()
+                        }
+                        error => panic!("Unexpected error {:?}", error),
+                    },
+                };
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+        }
+
+        /// Test attempting to go over capacity with alloc
+        #[test]
+        fn over_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<TestStruct>();
+            for index in 0..count {
+                let mut test =
+                    arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+            match arena.alloc(TestStruct { x: 0.0, y: 0.0 }) {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
+
+        /// Test attempting to go to capacity with alloc_zeroed
+        #[test]
+        fn at_capacity_alloc_zeroed() {
+            let capacity = 1024;
+            let count = capacity / size_of::<TestStruct>();
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            for index in 0..count {
+                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+        }
+
+        /// Test resetting after going over capacity with alloc_zeroed
+        #[test]
+        fn reset_at_capacity_alloc_zeroed() {
+            let capacity = 1024;
+            let count = capacity;
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            for index in 0..count {
+                let test = match arena.alloc_zeroed::<TestStruct>() {
+                    Ok(result) => result,
+                    Err(error) => match error {
+                        // if we're at capacity, reset and retry, panic if alloc
+                        // fails again
+                        AllocError::AtCapacity { .. } => {
+                            arena.reset();
+                            arena.alloc_zeroed::<TestStruct>().unwrap()
+                        }
+                        error => panic!("Unexpected error {:?}", error),
+                    },
+                };
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+        }
 
-        /// Test resetting the arena
+        // Test attempting to go over capacity with alloc_zeroed
+        #[test]
+        fn over_capacity_alloc_zeroed() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<TestStruct>();
+            for index in 0..count {
+                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
+                test.x = 15.0;
+                test.y = test.x + (index as f32);
+            }
+
+            match arena.alloc_zeroed::<TestStruct>() {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
+
+        /// Helper function for the mixed allocation test        
+        fn alloc_and_check<'a, T>(arena: &'a FixedArena, val: T) -> &'a mut T
+        where
+            T: PartialEq + Copy,
+        {
+            let result = arena.alloc(val).unwrap();
+            assert!(*result == val);
+
+            result
+        }
+    }
+
+    mod alloc_array {
+        use super::*;
+
+        /// Test allocating an array with an initial value
+        #[test]
+        fn alloc_array() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let test_array =
+                arena.alloc_array(I32Struct { x: 0, y: 0 }, 8).unwrap();
+            alloc_array_common(test_array);
+        }
+
+        /// Test allocating multiple arrays with initial values
+        #[test]
+        fn alloc_multiple_arrays() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / (2 * size_of::<I32Struct>());
+            let test_array_one =
+                arena.alloc_array(I32Struct { x: 0, y: 0 }, count).unwrap();
+            let test_array_two =
+                arena.alloc_array(I32Struct { x: 0, y: 0 }, count).unwrap();
+
+            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        }
+
+        /// Test allocating a single array to capacity
+        #[test]
+        fn alloc_array_to_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>();
+            let test_array =
+                arena.alloc_array(I32Struct { x: 1, y: -1 }, count).unwrap();
+
+            alloc_array_to_capacity_common(test_array, capacity);
+        }
+
+        /// Test attempting to allocate a single array to over capacity
+        #[test]
+        fn alloc_array_over_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let result = arena.alloc_array(
+                TestStruct { x: 0.0, y: 0.0 },
+                capacity / size_of::<TestStruct>() + 1,
+            );
+            alloc_array_over_capacity_common(result);
+        }
+
+        /// Test allocating an array to capacity, resetting, and then allocating
+        /// a new array
+        #[test]
+        fn alloc_array_to_capacity_reset() {
+            let capacity = 1024;
+            let count = capacity / size_of::<TestStruct>();
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            arena
+                .alloc_array(TestStruct { x: 0.0, y: 0.0 }, count)
+                .unwrap();
+
+            // attempt to alloc another array, should fail
+            match arena.alloc_array(TestStruct { x: 0.0, y: 0.0 }, count) {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+
+            arena.reset();
+
+            // second attempt should succeed
+            arena
+                .alloc_array(TestStruct { x: 0.0, y: 0.0 }, count)
+                .unwrap();
+        }
+
+        /// Test allocating a zeroed array
+        #[test]
+        fn alloc_zeroed_array() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let test_array = arena.alloc_zeroed_array::<I32Struct>(8).unwrap();
+
+            verify_i32_struct_array(test_array, 0, 0);
+
+            alloc_array_common(test_array);
+        }
+
+        /// Test allocating multiple zeroed arrays
+        #[test]
+        fn alloc_multiple_zeroed_array() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / (2 * size_of::<I32Struct>());
+            let test_array_one =
+                arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+            let test_array_two =
+                arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+
+            verify_i32_struct_array(&test_array_one, 0, 0);
+            verify_i32_struct_array(&test_array_two, 0, 0);
+
+            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        }
+
+        /// Test allocating a single zeroed array to capacity
+        #[test]
+        fn alloc_zeroed_array_to_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>();
+            let array = arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+            verify_i32_struct_array(array, 0, 0);
+
+            alloc_array_to_capacity_common(array, capacity);
+        }
+
+        /// Test allocating a single zeroed array over capacity
+        #[test]
+        fn alloc_zeroed_array_over_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = (capacity / size_of::<I32Struct>()) + 1;
+            match arena.alloc_zeroed_array::<I32Struct>(count) {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
+
+        /// Test allocating a zeroed array to capacity, resetting, and then
+        /// allocating a new zeroed array
+        #[test]
+        fn alloc_zeroed_array_to_capacity_reset() {
+            let capacity = 1024;
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>();
+            arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+
+            // should fail
+            match arena.alloc_zeroed_array::<I32Struct>(count) {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+
+            arena.reset();
+
+            let array = arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+            verify_i32_struct_array(array, 0, 0);
+        }
+
+        /// Test allocating an uninitialized value and writing to it
+        #[test]
+        fn alloc_uninit() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let value = arena.alloc_uninit::<I32Struct>().unwrap();
+            let value = value.write(I32Struct { x: 1, y: -1 });
+            assert_eq!(*value, I32Struct { x: 1, y: -1 });
+            assert_eq!(arena.used.get(), size_of::<I32Struct>());
+        }
+
+        /// Test allocating an uninitialized slice and initializing it
+        #[test]
+        fn alloc_uninit_slice() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let values = arena.alloc_uninit_slice::<I32Struct>(8).unwrap();
+            for value in values.iter_mut() {
+                value.write(I32Struct { x: 1, y: -1 });
+            }
+            let array = unsafe { uninit::assume_init_slice_mut(values) };
+
+            verify_i32_struct_array(array, 1, -1);
+        }
+
+        /// Test allocating an uninitialized slice over capacity
+        #[test]
+        fn alloc_uninit_slice_over_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>() + 1;
+            match arena.alloc_uninit_slice::<I32Struct>(count) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
+
+        /// Test allocating an uninitialized array
+        #[test]
+        fn alloc_unitialized_array() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let array =
+                unsafe { arena.alloc_uninitialized_array::<I32Struct>(8) }
+                    .unwrap();
+
+            alloc_array_common(array);
+        }
+
+        /// Test allocating multiple uninitialized arrays
+        #[test]
+        fn alloc_multiple_unitialized_array() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / (2 * size_of::<I32Struct>());
+            let test_array_one =
+                unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
+                    .unwrap();
+            let test_array_two =
+                unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
+                    .unwrap();
+
+            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        }
+
+        /// Test allocating a single uninitialized array to capacity
+        #[test]
+        fn alloc_unitialized_array_to_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>();
+            let array =
+                unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
+                    .unwrap();
+
+            alloc_array_to_capacity_common(array, capacity);
+        }
+
+        /// Test allocating a single uninitialized array over capacity
+        #[test]
+        fn alloc_unitialized_array_over_capacity() {
+            let capacity = 1024;
+            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>() + 1;
+            let result =
+                unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) };
+
+            alloc_array_over_capacity_common(result);
+        }
+
+        /// Test allocating an uninitialized array to capacity and then
+        /// resetting. Then allocate another unitialized array.
         #[test]
-        fn reset() {
-            let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        fn alloc_unitialized_array_to_capacity_reset() {
+            let capacity = 1024;
+            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
+            let count = capacity / size_of::<I32Struct>();
+            unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
+                .unwrap();
+
+            // should fail
+            match unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
             {
-                arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
-            }
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+
             arena.reset();
-            assert!(arena.used.get() == 0);
+
+            // second attempt should succeed
+            unsafe { arena.alloc_uninitialized_array::<I32Struct>(count) }
+                .unwrap();
+        }
+
+        /// Common code for testing allocating an array
+        /// Verifies the values were set to what was expected
+        fn alloc_array_common(array: &mut [I32Struct]) {
+            const X_VALUE: i32 = 1;
+            const Y_VALUE: i32 = -1;
+
+            for element in array.iter_mut() {
+                element.x = X_VALUE;
+                element.y = Y_VALUE;
+            }
+
+            verify_i32_struct_array(array, 1, -1);
+        }
+
+        /// Common code for testing allocating an array to capacity
+        fn alloc_array_to_capacity_common(
+            test_array: &mut [I32Struct],
+            capacity: usize,
+        ) {
+            assert_eq!(capacity % size_of::<I32Struct>(), 0);
+
+            const X_VALUE: i32 = 1;
+            const Y_VALUE: i32 = -1;
+
+            for element in test_array.iter_mut() {
+                element.x = X_VALUE;
+                element.y = Y_VALUE;
+            }
+
+            verify_i32_struct_array(test_array, X_VALUE, Y_VALUE);
+        }
+
+        /// Common code for testing allocating multiple arrays
+        /// This code will mutate the data, check the results, and also make
+        /// sure there isn't unexpected memory overlap between the two arrays
+        fn alloc_multiple_arrays_common(
+            test_array_one: &mut [I32Struct],
+            test_array_two: &mut [I32Struct],
+        ) {
+            const ARRAY_ONE_X_VALUE: i32 = 0x7FFFFFFF;
+            const ARRAY_ONE_Y_VALUE: i32 = -1;
+
+            const ARRAY_TWO_X_VALUE: i32 = 0x7ABABABA;
+            const ARRAY_TWO_Y_VALUE: i32 = -1 * 0x7ABABABA;
+
+            for test_value in test_array_one.iter_mut() {
+                test_value.x = ARRAY_ONE_X_VALUE;
+                test_value.y = ARRAY_ONE_Y_VALUE;
+            }
+            for test_value in test_array_two.iter_mut() {
+                test_value.x = ARRAY_TWO_X_VALUE;
+                test_value.y = ARRAY_TWO_Y_VALUE;
+            }
+
+            for test_value in test_array_one.iter() {
+                assert_eq!(test_value.x, ARRAY_ONE_X_VALUE);
+                assert_eq!(test_value.y, ARRAY_ONE_Y_VALUE);
+            }
+            for test_value in test_array_two.iter() {
+                assert_eq!(test_value.x, ARRAY_TWO_X_VALUE);
+                assert_eq!(test_value.y, ARRAY_TWO_Y_VALUE);
+            }
+
+            // make sure array_one can't overwrite array_two
+            for test_value in test_array_one.iter_mut() {
+                test_value.x = ARRAY_ONE_X_VALUE;
+                test_value.y = ARRAY_ONE_Y_VALUE;
+            }
+
+            for test_value in test_array_one.iter() {
+                assert_eq!(test_value.x, ARRAY_ONE_X_VALUE);
+                assert_eq!(test_value.y, ARRAY_ONE_Y_VALUE);
+            }
+            for test_value in test_array_two.iter() {
+                assert_eq!(test_value.x, ARRAY_TWO_X_VALUE);
+                assert_eq!(test_value.y, ARRAY_TWO_Y_VALUE);
+            }
+        }
+
+        /// Common code for testing allocating an array over capacity
+        fn alloc_array_over_capacity_common<T>(result: Result<T, AllocError>) {
+            match result {
+                Ok(_) => assert!(false),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
+
+        /// Common code for verifying an i32 structure
+        fn verify_i32_struct_array(
+            array: &[I32Struct],
+            expected_x_value: i32,
+            expected_y_value: i32,
+        ) {
+            for element in array {
+                assert_eq!(element.x, expected_x_value);
+                assert_eq!(element.y, expected_y_value);
+            }
+        }
+    }
+
+    mod alloc_with {
+        use super::*;
+
+        /// Test that the closure's value is written to the arena
+        #[test]
+        fn alloc_with() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena.alloc_with(|| TestStruct { x: 1.0, y: 2.0 });
+            assert_eq!(*result.unwrap(), TestStruct { x: 1.0, y: 2.0 });
+        }
+
+        /// Test a value too large to comfortably build on the stack
+        #[test]
+        fn large_value() {
+            let arena = FixedArena::with_capacity(1 << 20, 8);
+            let result = arena.alloc_with(|| [3_u64; 1 << 14]).unwrap();
+            assert!(result.iter().all(|value| *value == 3));
+        }
+
+        /// Test that the closure is not called when the arena is full
+        #[test]
+        fn at_capacity() {
+            let arena = FixedArena::with_capacity(4, DEFAULT_ALIGN);
+            match arena.alloc_with(|| -> u64 { panic!("should not be called") })
             {
-                let test_two =
-                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+        }
 
-                assert!(test_two.x == 1.0);
-                assert!(test_two.y == 2.0);
+        /// Test that a successful closure's value is written to the arena
+        #[test]
+        fn try_alloc_with() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena
+                .try_alloc_with(|| Ok::<_, ()>(SmallStruct { x: 5, y: 6 }))
+                .unwrap();
+            assert_eq!(*result, SmallStruct { x: 5, y: 6 });
+        }
+
+        /// Test that a failed closure gives back the memory and the padding
+        #[test]
+        fn try_alloc_with_rolls_back() {
+            let arena = FixedArena::with_capacity(1024, 8);
+            arena.alloc(1_u8).unwrap();
+            match arena.try_alloc_with(|| Err::<u64, _>("failed")) {
+                Ok(_) => panic!("initialization should fail"),
+                Err(err) => assert_eq!(err, AllocOrInitError::Init("failed")),
+            };
+            assert_eq!(arena.used.get(), 1);
+            assert_eq!(arena.padding(), 0);
+        }
+
+        /// Test that the memory is kept when the closure allocated from the
+        /// arena, since the error may refer to those allocations
+        #[test]
+        fn try_alloc_with_nested_allocation() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena.try_alloc_with(|| -> Result<u32, &mut u32> {
+                Err(arena.alloc(7).unwrap())
+            });
+            match result {
+                Ok(_) => panic!("initialization should fail"),
+                Err(AllocOrInitError::Init(value)) => {
+                    let next = arena.alloc(8_u32).unwrap();
+                    assert_eq!(*value, 7);
+                    assert_eq!(*next, 8);
+                }
+                Err(error) => panic!("Unexpected error {:?}", error),
+            };
+            assert_eq!(arena.used.get(), 12);
+        }
+
+        /// Test that the closure is not called when the arena is full
+        #[test]
+        fn try_alloc_with_at_capacity() {
+            let arena = FixedArena::with_capacity(4, DEFAULT_ALIGN);
+            let result = arena.try_alloc_with(|| -> Result<u64, ()> {
+                panic!("should not be called")
+            });
+            match result {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert!(matches!(
+                    err,
+                    AllocOrInitError::Alloc(AllocError::AtCapacity { .. })
+                )),
+            };
+        }
+    }
+
+    mod alloc_slice {
+        use super::*;
+        use std::{cell::RefCell, rc::Rc};
+
+        /// Records its id in a shared log when it is dropped
+        struct DropLogger {
+            id: u32,
+            log: Rc<RefCell<Vec<u32>>>,
+        }
+
+        impl Drop for DropLogger {
+            fn drop(&mut self) {
+                self.log.borrow_mut().push(self.id);
             }
         }
 
-        /// Test resetting the arena after allocating and mutating mutable data
+        /// Test copying a slice into the arena
         #[test]
-        fn reset_with_mut() {
+        fn copy() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let values = [1_u16, 2, 3];
+            let result = arena.alloc_slice_copy(&values).unwrap();
+            assert_eq!(result, values);
+            assert_eq!(arena.used.get(), size_of::<[u16; 3]>());
+        }
+
+        /// Test copying an empty slice
+        #[test]
+        fn copy_empty() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena.alloc_slice_copy::<u64>(&[]).unwrap();
+            assert!(result.is_empty());
+        }
+
+        /// Test that a slice that does not fit is not copied
+        #[test]
+        fn copy_at_capacity() {
+            let arena = FixedArena::with_capacity(8, DEFAULT_ALIGN);
+            match arena.alloc_slice_copy(&[1_u32, 2, 3]) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+            assert_eq!(arena.used.get(), 0);
+        }
+
+        /// Test cloning a slice into the arena
+        #[test]
+        fn clone() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let values =
+                [LargerStruct { x: 1, y: 2 }, LargerStruct { x: 4, y: 5 }];
+            let result = arena.alloc_slice_clone(&values).unwrap();
+            assert_eq!(result, values);
+        }
+
+        /// Test that the closure is called for each index in order
+        #[test]
+        fn fill_with() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let mut calls = Vec::new();
+            let result = arena
+                .alloc_slice_fill_with(5, |index| {
+                    calls.push(index);
+                    index as u32 * 3
+                })
+                .unwrap();
+            assert_eq!(result, [0, 3, 6, 9, 12]);
+            assert_eq!(calls, [0, 1, 2, 3, 4]);
+        }
+
+        /// Test that an exact size iterator uses exactly the space it needs
+        #[test]
+        fn from_iter_exact_size() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena.alloc_from_iter(0_u32..10).unwrap();
+            assert_eq!(result, (0..10).collect::<Vec<u32>>());
+            assert_eq!(arena.used.get(), size_of::<[u32; 10]>());
+        }
+
+        /// Test that an iterator of unknown size gives back the unused space
+        #[test]
+        fn from_iter_unknown_size() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena
+                .alloc_from_iter((0_u32..100).filter(|i| i % 10 == 0))
+                .unwrap();
+            assert_eq!(result, [0, 10, 20, 30, 40, 50, 60, 70, 80, 90]);
+            assert_eq!(arena.used.get(), size_of::<[u32; 10]>());
+            let next = arena.alloc(1_u32).unwrap();
+            assert_eq!(*next, 1);
+            assert_eq!(result[9], 90);
+        }
+
+        /// Test that an iterator of unknown size can fill the arena
+        #[test]
+        fn from_iter_fills_arena() {
+            let arena = FixedArena::with_capacity(40, DEFAULT_ALIGN);
+            let result =
+                arena.alloc_from_iter((0_u32..10).filter(|_| true)).unwrap();
+            assert_eq!(result.len(), 10);
+            assert_eq!(arena.used.get(), 40);
+        }
+
+        /// Test an iterator that allocates from the same arena
+        #[test]
+        fn from_iter_nested_allocations() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let result = arena
+                .alloc_from_iter(
+                    (0_u32..8)
+                        .filter(|_| true)
+                        .map(|i| &*arena.alloc(i).unwrap()),
+                )
+                .unwrap();
+            for (index, value) in result.iter().enumerate() {
+                assert_eq!(**value, index as u32);
+            }
+        }
+
+        /// Test that the produced values are dropped when the arena is full
+        #[test]
+        fn from_iter_at_capacity() {
+            let arena = FixedArena::with_capacity(
+                2 * size_of::<DropLogger>(),
+                align_of::<DropLogger>(),
+            );
+            let log = Rc::new(RefCell::new(Vec::new()));
+            let loggers = (0..3).filter(|_| true).map(|id| DropLogger {
+                id,
+                log: log.clone(),
+            });
+            match arena.alloc_from_iter(loggers) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
+            };
+            assert_eq!(*log.borrow(), [2, 0, 1]);
+            assert_eq!(arena.used.get(), 0);
+        }
+
+        /// Test that an iterator of zero sized values uses no space
+        #[test]
+        fn from_iter_zero_sized() {
+            let arena = FixedArena::with_capacity(8, DEFAULT_ALIGN);
+            let result = arena
+                .alloc_from_iter((0..100).filter(|_| true).map(|_| ()))
+                .unwrap();
+            assert_eq!(result.len(), 100);
+            assert_eq!(arena.used.get(), 0);
+        }
+    }
+
+    mod alloc_with_drop {
+        use super::*;
+        use std::sync::{Arc, Mutex};
+
+        /// Records its id in a shared log when it is dropped
+        struct DropLogger {
+            id: u32,
+            log: Arc<Mutex<Vec<u32>>>,
+        }
+
+        impl Drop for DropLogger {
+            fn drop(&mut self) {
+                self.log.lock().unwrap().push(self.id);
+            }
+        }
+
+        /// Test that registered destructors run in reverse order on reset
+        #[test]
+        fn drop_on_reset() {
+            let log = Arc::new(Mutex::new(Vec::new()));
             let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            for id in 0..3 {
+                let logger = arena
+                    .alloc_with_drop(DropLogger {
+                        id,
+                        log: log.clone(),
+                    })
+                    .unwrap();
+                assert_eq!(logger.id, id);
+            }
+            assert!(log.lock().unwrap().is_empty());
+
+            arena.reset();
+            assert_eq!(*log.lock().unwrap(), vec![2, 1, 0]);
 
+            // destructors only run once
+            arena.reset();
+            assert_eq!(*log.lock().unwrap(), vec![2, 1, 0]);
+        }
+
+        /// Test that registered destructors run when the arena is dropped
+        #[test]
+        fn drop_with_arena() {
+            let log = Arc::new(Mutex::new(Vec::new()));
             {
-                let mut test =
-                    arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
-                assert!(test.x == 0.0);
-                test.x = 1.0;
-                assert!(test.x == 1.0);
+                let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+                arena
+                    .alloc_with_drop(DropLogger {
+                        id: 0,
+                        log: log.clone(),
+                    })
+                    .unwrap();
+                arena.alloc_with_drop(String::from("dropped")).unwrap();
+                arena
+                    .alloc_with_drop(DropLogger {
+                        id: 1,
+                        log: log.clone(),
+                    })
+                    .unwrap();
             }
+            assert_eq!(*log.lock().unwrap(), vec![1, 0]);
+        }
 
+        /// Test that plain alloc does not run destructors
+        #[test]
+        fn alloc_does_not_drop() {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            let value: *mut DropLogger = arena
+                .alloc(DropLogger {
+                    id: 0,
+                    log: log.clone(),
+                })
+                .unwrap();
+            // Move the value out so that it can be dropped by hand instead of
+            // leaking. The arena never drops it, so it isn't dropped twice.
+            let value = unsafe { ptr::read(value) };
             arena.reset();
-            assert!(arena.used.get() == 0);
+            assert!(log.lock().unwrap().is_empty());
 
-            {
-                let test_two =
-                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+            drop(value);
+            assert_eq!(*log.lock().unwrap(), vec![0]);
+        }
 
-                assert!(test_two.x == 1.0);
-                assert!(test_two.y == 2.0);
-            }
+        /// Test that types without destructors don't use space for an entry
+        #[test]
+        fn no_entry_without_drop() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            arena
+                .alloc_with_drop(TestStruct { x: 0.0, y: 0.0 })
+                .unwrap();
+            assert_eq!(arena.used.get(), size_of::<TestStruct>());
+            assert!(arena.drops.get().is_null());
         }
 
-        /// Test resetting multiple times in the same loop
+        /// Test that a value that doesn't fit is dropped right away and the
+        /// arena is left unchanged
         #[test]
-        fn reset_in_loop() {
-            let capacity = 1024;
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            for index in 0..capacity {
-                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-                arena.reset();
-            }
+        fn over_capacity() {
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let arena = FixedArena::with_capacity(size_of::<DropLogger>(), 8);
+            let result = arena.alloc_with_drop(DropLogger {
+                id: 0,
+                log: log.clone(),
+            });
+            assert!(matches!(result, Err(AllocError::AtCapacity { .. })));
+            assert_eq!(*log.lock().unwrap(), vec![0]);
+            assert_eq!(arena.used.get(), 0);
+            assert!(arena.drops.get().is_null());
         }
     }
 
-    mod alloc_struct {
+    mod alloc_errors {
         use super::*;
+        use std::error::Error;
 
-        /// Test allocating a single struct
+        /// Test that AtCapacity reports the failed request
         #[test]
-        fn basic_allocation() {
-            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
-            {
-                let test = arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
-                assert!(test.x == 0.0);
-                assert!(test.y == 0.0);
-            }
+        fn at_capacity_context() {
+            let arena = FixedArena::with_capacity(16, 8);
+            arena.alloc(1_u32).unwrap();
+            match arena.alloc([1_u64, 2_u64]) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(
+                    err,
+                    AllocError::AtCapacity {
+                        requested: 16,
+                        align: 8,
+                        remaining: 12,
+                        top_callers: TopCallers::NONE,
+                    }
+                ),
+            };
         }
 
-        /// Test allocating a zeroed single struct
+        /// Test that array sizes that overflow return an error
         #[test]
-        fn alloc_zeroed() {
+        fn layout_overflow() {
             let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
-            {
-                let test = arena.alloc_zeroed::<TestStruct>().unwrap();
-                assert!(test.x == 0.0);
-                assert!(test.y == 0.0);
-            }
+            let count = usize::MAX / 4;
+            match arena.alloc_array(0_u64, count) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::LayoutOverflow),
+            };
+            match arena.alloc_zeroed_array::<u64>(count) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::LayoutOverflow),
+            };
+            match unsafe { arena.alloc_uninitialized_array::<u64>(count) } {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::LayoutOverflow),
+            };
         }
 
-        /// Test allocating multiple structurese with the alloc call
+        /// Test the display output of AtCapacity
         #[test]
-        fn multiple_allocation() {
-            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
-
-            {
-                let test = arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
-                let test_two =
-                    arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
-
-                assert!(test.x == 0.0);
-                assert!(test.y == 0.0);
-                assert!(test_two.x == 1.0);
-                assert!(test_two.y == 2.0);
-            }
+        fn display() {
+            let err = AllocError::AtCapacity {
+                requested: 16,
+                align: 8,
+                remaining: 12,
+                top_callers: TopCallers::NONE,
+            };
+            assert_eq!(
+                err.to_string(),
+                "arena is at capacity: requested 16 bytes aligned to 8, but \
+                 only 12 bytes remain"
+            );
         }
 
-        /// Test allocating multiple different structures with different
-        /// alignments
+        /// Test that errors can be propagated as boxed errors
         #[test]
-        fn mixed_allocation() {
-            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        fn boxed_error() {
+            fn fill(arena: &FixedArena) -> Result<(), Box<dyn Error>> {
+                loop {
+                    arena.alloc(0_u64)?;
+                }
+            }
 
-            let first = TestStruct { x: 1.0, y: -1.0 };
-            let first_result = alloc_and_check(&arena, first);
+            let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+            let err = fill(&arena).unwrap_err();
+            assert!(err.downcast_ref::<AllocError>().is_some());
+        }
+    }
 
-            let second = LargerStruct {
-                x: (1 << 42),
-                y: -1 * (1 << 42),
-            };
-            let second_result = alloc_and_check(&arena, second);
+    mod resize_last {
+        use super::*;
 
-            let third = SmallerStruct {
-                x: 1 << 9,
-                y: -1 * (1 << 9),
-            };
-            let third_result = alloc_and_check(&arena, third);
+        fn as_bytes<T>(value: &mut T) -> NonNull<u8> {
+            NonNull::from(value).cast::<u8>()
+        }
 
-            let fourth = SmallStruct { x: 127, y: -1 };
-            let fourth_result = alloc_and_check(&arena, fourth);
+        /// Test that the last allocation can grow into the free space
+        #[test]
+        fn grow() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let value = arena.alloc(1_u64).unwrap();
+            arena.try_grow_last(as_bytes(value), 8, 24, 8).unwrap();
+            assert_eq!(arena.used.get(), 24);
+            assert_eq!(*value, 1);
+        }
 
-            let fifth = MixedStruct {
-                a: 1 << 33,
-                b: 1 << 17,
-                c: 1 << 9,
-                d: 127,
-                e: 1.000454846,
-                f: -1.000454846,
-                g: 0xFFFF,
-                h: 0xFF,
+        /// Test that growing past the capacity fails and leaves the arena
+        /// unchanged
+        #[test]
+        fn grow_at_capacity() {
+            let arena = FixedArena::with_capacity(16, 8);
+            let value = arena.alloc(1_u64).unwrap();
+            match arena.try_grow_last(as_bytes(value), 8, 24, 8) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(
+                    err,
+                    AllocError::AtCapacity {
+                        requested: 24,
+                        align: 8,
+                        remaining: 16,
+                        top_callers: TopCallers::NONE,
+                    }
+                ),
             };
-            let fifth_result = alloc_and_check(&arena, fifth);
+            assert_eq!(arena.used.get(), 8);
+        }
 
-            assert!(first == *first_result);
-            assert!(second == *second_result);
-            assert!(third == *third_result);
-            assert!(fourth == *fourth_result);
-            assert!(fifth == *fifth_result);
+        /// Test that only the most recent allocation can grow
+        #[test]
+        fn grow_not_last() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let first = arena.alloc(1_u64).unwrap();
+            arena.alloc(2_u64).unwrap();
+            match arena.try_grow_last(as_bytes(first), 8, 16, 8) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::NotLast),
+            };
+            assert_eq!(arena.used.get(), 16);
         }
 
-        /// Test filling up the arena to capacity
+        /// Test that a size larger than the used bytes is not treated as the
+        /// last allocation
         #[test]
-        fn at_capacity() {
-            let capacity = 1024;
-            let count = capacity / size_of::<TestStruct>();
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            for index in 0..count {
-                let mut test =
-                    arena.alloc(TestStruct { x: 1.0, y: -1.0 }).unwrap();
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-            }
+        fn grow_size_too_large() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let value = arena.alloc(1_u64).unwrap();
+            let pointer = as_bytes(value);
+            match arena.try_grow_last(pointer, 16, 32, 8) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::NotLast),
+            };
         }
 
-        /// Test resetting the arena after filling it up to capacity
-        /// Should be able to continue allocating
+        /// Test that shrinking gives the tail back to the arena
         #[test]
-        fn reset_at_capacity() {
-            let capacity = 1024;
-            let count = capacity;
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            for index in 0..count {
-                let test = match arena.alloc(TestStruct { x: 1.0, y: -1.0 }) {
-                    Ok(result) => result,
-                    Err(error) => match error {
-                        // if we're at capacity, reset and retry, panic if alloc
-                        // fails again
-                        AllocError::AtCapacity => {
-                            arena.reset();
-                            arena.alloc(TestStruct { x: 1.0, y: -1.0 }).unwrap()
-                        }
-                    },
-                };
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-            }
+        fn shrink() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let values = arena.alloc_array(3_u64, 4).unwrap();
+            let pointer = NonNull::from(values).cast::<u8>();
+            unsafe { arena.shrink_last(pointer, 32, 8).unwrap() };
+            assert_eq!(arena.used.get(), 8);
+            let next = arena.alloc(4_u64).unwrap();
+            assert_eq!(*next, 4);
+            assert_eq!(unsafe { *pointer.cast::<u64>().as_ptr() }, 3);
         }
 
-        /// Test attempting to go over capacity with alloc
+        /// Test that only the most recent allocation can shrink
         #[test]
-        fn over_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<TestStruct>();
-            for index in 0..count {
-                let mut test =
-                    arena.alloc(TestStruct { x: 0.0, y: 0.0 }).unwrap();
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-            }
-            match arena.alloc(TestStruct { x: 0.0, y: 0.0 }) {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
+        fn shrink_not_last() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let values = arena.alloc_array(3_u64, 4).unwrap();
+            arena.alloc(1_u8).unwrap();
+            let pointer = NonNull::from(values).cast::<u8>();
+            match unsafe { arena.shrink_last(pointer, 32, 8) } {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(err, AllocError::NotLast),
             };
+            assert_eq!(arena.used.get(), 33);
         }
 
-        /// Test attempting to go to capacity with alloc_zeroed
+        /// Test that popping frees the most recent allocation
         #[test]
-        fn at_capacity_alloc_zeroed() {
-            let capacity = 1024;
-            let count = capacity / size_of::<TestStruct>();
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            for index in 0..count {
-                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-            }
+        fn pop() {
+            let arena = FixedArena::with_capacity(16, 8);
+            arena.alloc(1_u64).unwrap();
+            let value = arena.alloc(2_u64).unwrap();
+            unsafe { arena.pop_last(as_bytes(value), 8).unwrap() };
+            assert_eq!(arena.used.get(), 8);
+            let value = arena.alloc(3_u64).unwrap();
+            assert_eq!(*value, 3);
         }
 
-        /// Test resetting after going over capacity with alloc_zeroed
+        /// Test that allocations can be popped one after another
         #[test]
-        fn reset_at_capacity_alloc_zeroed() {
-            let capacity = 1024;
-            let count = capacity;
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            for index in 0..count {
-                let test = match arena.alloc_zeroed::<TestStruct>() {
-                    Ok(result) => result,
-                    Err(error) => match error {
-                        // if we're at capacity, reset and retry, panic if alloc
-                        // fails again
-                        AllocError::AtCapacity => {
-                            arena.reset();
-                            arena.alloc_zeroed::<TestStruct>().unwrap()
-                        }
-                    },
+        fn pop_repeatedly() {
+            let arena = FixedArena::with_capacity(16, 8);
+            let first = as_bytes(arena.alloc(1_u64).unwrap());
+            let second = as_bytes(arena.alloc(2_u64).unwrap());
+            unsafe {
+                match arena.pop_last(first, 8) {
+                    Ok(_) => panic!("allocation should fail"),
+                    Err(err) => assert_eq!(err, AllocError::NotLast),
                 };
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
+                arena.pop_last(second, 8).unwrap();
+                arena.pop_last(first, 8).unwrap();
             }
+            assert_eq!(arena.used.get(), 0);
         }
+    }
 
-        // Test attempting to go over capacity with alloc_zeroed
+    mod usage {
+        use super::*;
+
+        /// Test that used and remaining follow allocations and resets
         #[test]
-        fn over_capacity_alloc_zeroed() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<TestStruct>();
-            for index in 0..count {
-                let mut test = arena.alloc_zeroed::<TestStruct>().unwrap();
-                test.x = 15.0;
-                test.y = test.x + (index as f32);
-            }
+        fn used_and_remaining() {
+            let mut arena = FixedArena::with_capacity(64, 8);
+            assert_eq!(arena.capacity(), 64);
+            assert_eq!(arena.used(), 0);
+            assert_eq!(arena.remaining(), 64);
 
-            match arena.alloc_zeroed::<TestStruct>() {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
-            };
-        }
+            arena.alloc(1_u8).unwrap();
+            arena.alloc(1_u64).unwrap();
+            assert_eq!(arena.used(), 16);
+            assert_eq!(arena.remaining(), 48);
 
-        /// Helper function for the mixed allocation test        
-        fn alloc_and_check<T>(arena: &FixedArena, val: T) -> &mut T
-        where
-            T: PartialEq + Copy,
-        {
-            let result = arena.alloc(val).unwrap();
-            assert!(*result == val);
+            arena.reset();
+            assert_eq!(arena.used(), 0);
+            assert_eq!(arena.remaining(), 64);
+        }
 
-            result
+        /// Test that an arena over a buffer reports the buffer length
+        #[test]
+        fn from_buffer_capacity() {
+            let mut buffer = [MaybeUninit::uninit(); 100];
+            let arena = FixedArena::from_buffer(&mut buffer);
+            assert_eq!(arena.capacity(), 100);
+            assert_eq!(arena.remaining(), 100);
         }
     }
 
-    mod alloc_array {
+    #[cfg(feature = "stats")]
+    mod stats {
         use super::*;
+        use std::sync::Arc;
 
-        /// Test allocating an array with an initial value
+        /// Test that the high water mark survives resets and rollbacks
         #[test]
-        fn alloc_array() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let test_array =
-                arena.alloc_array(I32Struct { x: 0, y: 0 }, 8).unwrap();
-            alloc_array_common(test_array);
+        fn high_water_mark() {
+            let mut arena = FixedArena::with_capacity(1024, 8);
+            arena.alloc_array(0_u8, 100).unwrap();
+            arena.reset();
+            arena.alloc_array(0_u8, 50).unwrap();
+            assert_eq!(arena.stats().high_water_mark, 100);
+
+            {
+                let scope = arena.scope();
+                scope.alloc_array(0_u8, 200).unwrap();
+            }
+            assert_eq!(arena.used(), 50);
+            assert_eq!(arena.stats().high_water_mark, 250);
+
+            arena.alloc_array(0_u8, 300).unwrap();
+            assert_eq!(arena.stats().high_water_mark, 350);
         }
 
-        /// Test allocating multiple arrays with initial values
+        /// Test that shrinking the last allocation keeps the high water mark
         #[test]
-        fn alloc_multiple_arrays() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / (2 * size_of::<I32Struct>());
-            let test_array_one =
-                arena.alloc_array(I32Struct { x: 0, y: 0 }, count).unwrap();
-            let test_array_two =
-                arena.alloc_array(I32Struct { x: 0, y: 0 }, count).unwrap();
+        fn high_water_mark_shrink() {
+            let arena = FixedArena::with_capacity(1024, 8);
+            let values = arena.alloc_array(0_u8, 100).unwrap();
+            let pointer = NonNull::from(values).cast::<u8>();
+            unsafe { arena.shrink_last(pointer, 100, 10).unwrap() };
+            assert_eq!(arena.stats().high_water_mark, 100);
+        }
 
-            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        /// Test counting successful and failed allocations
+        #[test]
+        fn allocations() {
+            let arena = FixedArena::with_capacity(64, 8);
+            arena.alloc(1_u32).unwrap();
+            arena.alloc_array(0_u16, 4).unwrap();
+            arena.alloc_with_drop(Arc::new(1)).unwrap();
+            arena.alloc_array(0_u8, 64).unwrap_err();
+
+            let stats = arena.stats();
+            assert_eq!(stats.allocations, 3);
+            assert_eq!(stats.failed_allocations, 1);
         }
 
-        /// Test allocating a single array to capacity
+        /// Test that the allocation given back by a failed try_alloc_with is
+        /// not counted
         #[test]
-        fn alloc_array_to_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>();
-            let test_array =
-                arena.alloc_array(I32Struct { x: 1, y: -1 }, count).unwrap();
+        fn try_alloc_with_failure() {
+            let arena = FixedArena::with_capacity(64, 8);
+            arena.alloc(1_u8).unwrap();
+            let result = arena.try_alloc_with(|| Err::<u64, _>("failed"));
+            assert!(result.is_err());
+
+            let stats = arena.stats();
+            assert_eq!(stats.allocations, 1);
+            assert_eq!(stats.padding, 0);
+            assert_eq!(stats.high_water_mark, 1);
+        }
 
-            alloc_array_to_capacity_common(test_array, capacity);
+        /// Test that a value that fits without room for its drop entry is
+        /// counted as a single failed allocation
+        #[test]
+        fn alloc_with_drop_failure() {
+            let arena = FixedArena::with_capacity(16, 8);
+            arena.alloc(1_u64).unwrap();
+            arena.alloc_with_drop(Arc::new(1)).unwrap_err();
+
+            let stats = arena.stats();
+            assert_eq!(stats.allocations, 1);
+            assert_eq!(stats.failed_allocations, 1);
+            assert_eq!(stats.high_water_mark, 8);
         }
 
-        /// Test attempting to allocate a single array to over capacity
+        /// Test that padding is totalled across resets
         #[test]
-        fn alloc_array_over_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let result = arena.alloc_array(
-                TestStruct { x: 0.0, y: 0.0 },
-                capacity / size_of::<TestStruct>() + 1,
-            );
-            alloc_array_over_capacity_common(result);
+        fn padding_and_resets() {
+            let mut arena = FixedArena::with_capacity(64, 8);
+            for _ in 0..3 {
+                arena.alloc(1_u8).unwrap();
+                arena.alloc(1_u32).unwrap();
+                arena.reset();
+            }
+
+            let stats = arena.stats();
+            assert_eq!(stats.padding, 9);
+            assert_eq!(stats.resets, 3);
+            assert_eq!(arena.padding(), 0);
+        }
+    }
+
+    #[cfg(feature = "track-callers")]
+    mod callers {
+        use super::*;
+        use crate::{string::ArenaString, vec::ArenaVec};
+
+        /// Test that allocations are totaled by the line that made them
+        #[test]
+        fn report() {
+            let arena = FixedArena::with_capacity(1024, 8);
+            let small_line = line!() + 2;
+            for _ in 0..3 {
+                arena.alloc(0_u8).unwrap();
+            }
+            let large_line = line!() + 1;
+            arena.alloc_array(0_u64, 4).unwrap();
+
+            let report = arena.report();
+            let sites: Vec<_> = report
+                .iter()
+                .map(|site| {
+                    (site.location.line(), site.bytes, site.allocations)
+                })
+                .collect();
+            // The array is aligned after the 3 bytes, so it also takes 5
+            // bytes of padding
+            assert_eq!(sites, [(large_line, 37, 1), (small_line, 3, 3)]);
+            assert!(report.iter().all(|site| site.location.file() == file!()));
+        }
+
+        /// Test that a failed allocation is not counted for its call site
+        #[test]
+        fn at_capacity() {
+            let arena = FixedArena::with_capacity(32, 8);
+            let line = line!() + 1;
+            arena.alloc([0_u64; 3]).unwrap();
+            arena.alloc([0_u64; 2]).unwrap_err();
+            let report = arena.report();
+            let sites: Vec<_> = report
+                .iter()
+                .map(|site| (site.location.line(), site.bytes))
+                .collect();
+            assert_eq!(sites, [(line, 24)]);
+        }
+
+        /// Test that the top callers are listed in AtCapacity
+        #[test]
+        fn top_callers() {
+            let arena = FixedArena::with_capacity(32, 8);
+            let line = line!() + 1;
+            arena.alloc([0_u64; 3]).unwrap();
+            let err = arena.alloc([0_u64; 2]).unwrap_err();
+            let AllocError::AtCapacity { top_callers, .. } = err else {
+                panic!("allocation should fail with AtCapacity");
+            };
+            let site = top_callers.iter().next().unwrap();
+            assert_eq!((site.location.line(), site.bytes), (line, 24));
+            assert!(err.to_string().contains(&format!(
+                "; top callers: {}:{}:",
+                file!(),
+                line
+            )));
+
+            // Growing in place lists them too
+            let arena = FixedArena::with_capacity(32, 8);
+            let line = line!() + 1;
+            let value = arena.alloc(0_u64).unwrap();
+            let pointer = NonNull::from(value).cast();
+            let err = arena.try_grow_last(pointer, 8, 64, 8).unwrap_err();
+            assert!(err.to_string().contains(&format!(
+                "; top callers: {}:{}:",
+                file!(),
+                line
+            )));
         }
 
-        /// Test allocating an array to capacity, resetting, and then allocating
-        /// a new array
+        /// Test that growing vectors and strings counts for their caller
         #[test]
-        fn alloc_array_to_capacity_reset() {
-            let capacity = 1024;
-            let count = capacity / size_of::<TestStruct>();
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            arena
-                .alloc_array(TestStruct { x: 0.0, y: 0.0 }, count)
-                .unwrap();
+        fn containers() {
+            let arena = FixedArena::with_capacity(1024, 8);
+            let mut values = ArenaVec::new_in(&arena);
+            let vec_line = line!() + 2;
+            for value in 0..16_u32 {
+                values.push(value).unwrap();
+            }
+            let mut string = ArenaString::new_in(&arena);
+            let string_line = line!() + 1;
+            string.push_str("text").unwrap();
+
+            let report = arena.report();
+            let lines: Vec<_> =
+                report.iter().map(|site| site.location.line()).collect();
+            assert_eq!(lines, [vec_line, string_line]);
+            assert_eq!(report.iter().next().unwrap().bytes, 64);
+        }
 
-            // attempt to alloc another array, should fail
-            match arena.alloc_array(TestStruct { x: 0.0, y: 0.0 }, count) {
-                Ok(_) => assert!(false),
-                Err(err) => assert!(err == AllocError::AtCapacity),
-            };
+        /// Test that the allocation given back by a failed try_alloc_with is
+        /// taken off the totals of its call site
+        #[test]
+        fn try_alloc_with_failure() {
+            let arena = FixedArena::with_capacity(64, 8);
+            for value in [Ok(1_u64), Err("failed")] {
+                let _ = arena.try_alloc_with(|| value);
+            }
+            let site = *arena.report().iter().next().unwrap();
+            assert_eq!((site.bytes, site.allocations), (8, 1));
+        }
 
+        /// Test that a reset clears the totals
+        #[test]
+        fn reset() {
+            let mut arena = FixedArena::with_capacity(1024, 8);
+            arena.alloc(1_u64).unwrap();
             arena.reset();
+            assert_eq!(arena.report().iter().count(), 0);
+            assert_eq!(arena.report().to_string(), "");
+        }
+    }
 
-            // second attempt should succeed
+    #[cfg(feature = "red-zone")]
+    mod red_zone {
+        use super::*;
+        use crate::red_zone::{Overrun, CANARY_SIZE};
+
+        const RECORD_SIZE: usize = mem::size_of::<RedZone>();
+        /// The bytes used by a red zone allocation of a `u64`
+        const U64_BLOCK: usize = RECORD_SIZE + 8 + CANARY_SIZE;
+
+        /// Make an arena with red zones enabled
+        fn red_zone_arena(capacity: usize) -> FixedArena<'static> {
+            let mut arena = FixedArena::with_capacity(capacity, 8);
+            arena.set_red_zones(true);
             arena
-                .alloc_array(TestStruct { x: 0.0, y: 0.0 }, count)
-                .unwrap();
         }
 
-        /// Test allocating a zeroed array
+        /// Write over the byte at `offset` in the arena, like an overrun
+        fn overwrite(arena: &FixedArena, offset: usize) {
+            unsafe { arena.base.get().add(offset).write(0) };
+        }
+
+        /// Test that an allocation is placed between its record and canary
         #[test]
-        fn alloc_zeroed_array() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let test_array = arena.alloc_zeroed_array::<I32Struct>(8).unwrap();
+        fn layout() {
+            let arena = red_zone_arena(1024);
+            let value = arena.alloc(1_u64).unwrap() as *mut u64;
+            assert_eq!(
+                value as *mut u8,
+                arena.base.get().wrapping_add(RECORD_SIZE)
+            );
+            assert_eq!(arena.used(), U64_BLOCK);
+            assert_eq!(arena.verify(), Ok(()));
+        }
 
-            verify_i32_struct_array(test_array, 0, 0);
+        /// Test that an overwritten canary is reported with its allocation
+        #[test]
+        fn overrun() {
+            let arena = red_zone_arena(1024);
+            arena.alloc_array(0_u32, 4).unwrap();
+            arena.alloc(1_u64).unwrap();
+            overwrite(&arena, RECORD_SIZE + 16);
+
+            let overrun = Overrun {
+                offset: RECORD_SIZE,
+                size: 16,
+                type_name: "[u32]",
+            };
+            assert_eq!(arena.verify(), Err(overrun));
+            assert_eq!(
+                overrun.to_string(),
+                format!(
+                    "canary overwritten after the 16 byte allocation of \
+                     [u32] at offset {}",
+                    RECORD_SIZE
+                )
+            );
+        }
 
-            alloc_array_common(test_array);
+        /// Test that the first overrun in the arena is reported
+        #[test]
+        fn first_overrun() {
+            let arena = red_zone_arena(1024);
+            for value in 0..3_u64 {
+                arena.alloc(value).unwrap();
+            }
+            overwrite(&arena, 2 * U64_BLOCK + RECORD_SIZE + 8);
+            overwrite(&arena, U64_BLOCK + RECORD_SIZE + 8 + CANARY_SIZE - 1);
+
+            let overrun = arena.verify().unwrap_err();
+            assert_eq!(overrun.offset, U64_BLOCK + RECORD_SIZE);
+            assert_eq!(overrun.type_name, "u64");
         }
 
-        /// Test allocating multiple zeroed arrays
+        /// Test that an overrun past the canary reports the record it wrote
+        /// over
         #[test]
-        fn alloc_multiple_zeroed_array() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / (2 * size_of::<I32Struct>());
-            let test_array_one =
-                arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
-            let test_array_two =
-                arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
+        fn overrun_into_record() {
+            let arena = red_zone_arena(1024);
+            arena.alloc(1_u64).unwrap();
+            arena.alloc(2_u64).unwrap();
+            for offset in RECORD_SIZE + 8..U64_BLOCK + 8 {
+                overwrite(&arena, offset);
+            }
 
-            verify_i32_struct_array(&test_array_one, 0, 0);
-            verify_i32_struct_array(&test_array_two, 0, 0);
+            let overrun = arena.verify().unwrap_err();
+            assert_eq!(overrun.offset, U64_BLOCK);
+            assert_eq!(overrun.size, RECORD_SIZE);
+        }
 
-            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        /// Test that the red zones of released allocations are not checked
+        #[test]
+        fn release() {
+            let mut arena = red_zone_arena(1024);
+            arena.alloc(1_u64).unwrap();
+            let checkpoint = arena.checkpoint();
+            arena.alloc(2_u64).unwrap();
+            overwrite(&arena, U64_BLOCK + RECORD_SIZE + 8);
+            assert!(arena.verify().is_err());
+
+            arena.rollback(checkpoint);
+            assert_eq!(arena.verify(), Ok(()));
+            arena.alloc(3_u64).unwrap();
+            assert_eq!(arena.verify(), Ok(()));
+
+            overwrite(&arena, RECORD_SIZE + 8);
+            arena.reset();
+            assert_eq!(arena.verify(), Ok(()));
         }
 
-        /// Test allocating a single zeroed array to capacity
+        /// Test that the canary moves with the end of a resized allocation
         #[test]
-        fn alloc_zeroed_array_to_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>();
-            let array = arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
-            verify_i32_struct_array(array, 0, 0);
+        fn resize_last() {
+            let arena = red_zone_arena(1024);
+            let values = arena.alloc_array(0_u64, 2).unwrap();
+            let pointer = NonNull::from(values).cast::<u8>();
+
+            arena.try_grow_last(pointer, 16, 32, 8).unwrap();
+            assert_eq!(arena.used(), RECORD_SIZE + 32 + CANARY_SIZE);
+            assert_eq!(arena.verify(), Ok(()));
+
+            unsafe { arena.shrink_last(pointer, 32, 8).unwrap() };
+            assert_eq!(arena.used(), U64_BLOCK);
+            assert_eq!(arena.verify(), Ok(()));
+            overwrite(&arena, RECORD_SIZE + 8);
+            assert_eq!(arena.verify().unwrap_err().size, 8);
+        }
 
-            alloc_array_to_capacity_common(array, capacity);
+        /// Test growing a vector in place with red zones
+        #[test]
+        fn vec() {
+            let arena = red_zone_arena(1024);
+            let mut values = ArenaVec::new_in(&arena);
+            for value in 0..16_u32 {
+                values.push(value).unwrap();
+            }
+            assert_eq!(arena.verify(), Ok(()));
+            assert_eq!(values.iter().sum::<u32>(), 120);
         }
 
-        /// Test allocating a single zeroed array over capacity
+        /// Test that a failed allocation leaves the arena unchanged and
+        /// reports the requested size
         #[test]
-        fn alloc_zeroed_array_over_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = (capacity / size_of::<I32Struct>()) + 1;
-            match arena.alloc_zeroed_array::<I32Struct>(count) {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
+        fn at_capacity() {
+            let arena = red_zone_arena(U64_BLOCK + 8);
+            arena.alloc(1_u64).unwrap();
+            match arena.alloc(2_u64) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => assert_eq!(
+                    err,
+                    AllocError::AtCapacity {
+                        requested: 8,
+                        align: 8,
+                        remaining: 8,
+                        top_callers: TopCallers::NONE,
+                    }
+                ),
             };
+            assert_eq!(arena.used(), U64_BLOCK);
+            assert_eq!(arena.verify(), Ok(()));
         }
 
-        /// Test allocating a zeroed array to capacity, resetting, and then
-        /// allocating a new zeroed array
+        /// Test that allocations made with red zones turned off have none
         #[test]
-        fn alloc_zeroed_array_to_capacity_reset() {
-            let capacity = 1024;
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>();
-            arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
-
-            // should fail
-            match arena.alloc_zeroed_array::<I32Struct>(count) {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
-            };
+        fn disabled() {
+            let mut arena = red_zone_arena(1024);
+            arena.alloc(1_u64).unwrap();
+            arena.set_red_zones(false);
+            arena.alloc(2_u64).unwrap();
+            assert_eq!(arena.used(), U64_BLOCK + 8);
+            assert_eq!(arena.verify(), Ok(()));
+        }
+    }
 
-            arena.reset();
+    // Reading released memory is an error under AddressSanitizer
+    #[cfg(all(feature = "debug-poison", not(sanitize = "address")))]
+    mod poison {
+        use super::*;
+        use crate::{poison, vec::ArenaVec};
 
-            let array = arena.alloc_zeroed_array::<I32Struct>(count).unwrap();
-            verify_i32_struct_array(array, 0, 0);
+        /// Read the bytes of the arena from `start` to `end`
+        fn bytes(arena: &FixedArena, start: usize, end: usize) -> Vec<u8> {
+            unsafe {
+                slice::from_raw_parts(arena.base.get().add(start), end - start)
+            }
+            .to_vec()
         }
 
-        /// Test allocating an uninitialized array
+        /// Test that reset fills the released memory
         #[test]
-        fn alloc_unitialized_array() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let array =
-                arena.alloc_uninitialized_array::<I32Struct>(8).unwrap();
-
-            alloc_array_common(array);
+        fn reset() {
+            let mut arena = FixedArena::with_capacity(64, 8);
+            arena.alloc_array(1_u8, 40).unwrap();
+            arena.reset();
+            assert!(bytes(&arena, 0, 40).iter().all(|b| *b == poison::FREED));
         }
 
-        /// Test allocating multiple uninitialized arrays
+        /// Test that a rollback only fills the memory it released
         #[test]
-        fn alloc_multiple_unitialized_array() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / (2 * size_of::<I32Struct>());
-            let test_array_one =
-                arena.alloc_uninitialized_array::<I32Struct>(count).unwrap();
-            let test_array_two =
-                arena.alloc_uninitialized_array::<I32Struct>(count).unwrap();
+        fn rollback() {
+            let mut arena = FixedArena::with_capacity(64, 8);
+            arena.alloc_array(1_u8, 8).unwrap();
+            let checkpoint = arena.checkpoint();
+            arena.alloc_array(2_u8, 8).unwrap();
+            arena.rollback(checkpoint);
+            assert_eq!(bytes(&arena, 0, 8), [1; 8]);
+            assert_eq!(bytes(&arena, 8, 16), [poison::FREED; 8]);
+        }
 
-            alloc_multiple_arrays_common(test_array_one, test_array_two);
+        /// Test that dropping a vector fills the memory it gave back
+        #[test]
+        fn vec_drop() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let mut values = ArenaVec::new_in(&arena);
+            values.extend_from_slice(&[1_u8; 16]).unwrap();
+            drop(values);
+            assert_eq!(arena.used(), 0);
+            assert_eq!(bytes(&arena, 0, 16), [poison::FREED; 16]);
         }
 
-        /// Test allocating a single uninitialized array to capacity
+        /// Test that uninitialized allocations are filled
         #[test]
-        fn alloc_unitialized_array_to_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>();
-            let array =
-                arena.alloc_uninitialized_array::<I32Struct>(count).unwrap();
+        fn uninit() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let value = arena.alloc_uninit::<u32>().unwrap();
+            assert_eq!(
+                unsafe { value.assume_init() },
+                u32::from_ne_bytes([poison::UNINIT; 4])
+            );
+            let values =
+                unsafe { arena.alloc_uninitialized_array::<u16>(4) }.unwrap();
+            let pattern = u16::from_ne_bytes([poison::UNINIT; 2]);
+            assert_eq!(values, [pattern; 4]);
+        }
+    }
 
-            alloc_array_to_capacity_common(array, capacity);
+    #[cfg(sanitize = "address")]
+    mod asan {
+        use super::*;
+
+        unsafe extern "C" {
+            fn __asan_address_is_poisoned(
+                addr: *const core::ffi::c_void,
+            ) -> core::ffi::c_int;
         }
 
-        /// Test allocating a single uninitialized array over capacity
-        #[test]
-        fn alloc_unitialized_array_over_capacity() {
-            let capacity = 1024;
-            let arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>() + 1;
-            let result = arena.alloc_uninitialized_array::<I32Struct>(count);
+        /// Check whether the byte at `offset` in the arena is poisoned
+        fn is_poisoned(arena: &FixedArena, offset: usize) -> bool {
+            let pointer = unsafe { arena.base.get().add(offset) };
+            unsafe { __asan_address_is_poisoned(pointer.cast()) != 0 }
+        }
 
-            alloc_array_over_capacity_common(result);
+        /// Test that only allocated memory is unpoisoned
+        #[test]
+        fn alloc() {
+            let arena = FixedArena::with_capacity(64, 8);
+            assert!(is_poisoned(&arena, 0));
+            arena.alloc(1_u64).unwrap();
+            arena.alloc(2_u64).unwrap();
+            assert!(!is_poisoned(&arena, 0));
+            assert!(!is_poisoned(&arena, 15));
+            assert!(is_poisoned(&arena, 16));
+            assert!(is_poisoned(&arena, 63));
         }
 
-        /// Test allocating an uninitialized array to capacity and then
-        /// resetting. Then allocate another unitialized array.
+        /// Test that padding between allocations stays poisoned
         #[test]
-        fn alloc_unitialized_array_to_capacity_reset() {
-            let capacity = 1024;
-            let mut arena = FixedArena::with_capacity(capacity, DEFAULT_ALIGN);
-            let count = capacity / size_of::<I32Struct>();
-            arena.alloc_uninitialized_array::<I32Struct>(count).unwrap();
+        fn padding() {
+            let arena = FixedArena::with_capacity(64, 8);
+            arena.alloc(1_u8).unwrap();
+            arena.alloc(2_u64).unwrap();
+            assert!(!is_poisoned(&arena, 0));
+            assert!(is_poisoned(&arena, 1));
+            assert!(is_poisoned(&arena, 7));
+            assert!(!is_poisoned(&arena, 8));
+        }
 
-            // should fail
-            match arena.alloc_uninitialized_array::<I32Struct>(count) {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
-            };
+        /// Test that reset and rollback poison the released memory
+        #[test]
+        fn release() {
+            let mut arena = FixedArena::with_capacity(64, 8);
+            arena.alloc(1_u64).unwrap();
+            let checkpoint = arena.checkpoint();
+            arena.alloc(2_u64).unwrap();
+            arena.rollback(checkpoint);
+            assert!(!is_poisoned(&arena, 0));
+            assert!(is_poisoned(&arena, 8));
 
             arena.reset();
-
-            // second attempt should succeed
-            arena.alloc_uninitialized_array::<I32Struct>(count).unwrap();
+            assert!(is_poisoned(&arena, 0));
         }
 
-        /// Common code for testing allocating an array
-        /// Verifies the values were set to what was expected
-        fn alloc_array_common(array: &mut [I32Struct]) {
-            const X_VALUE: i32 = 1;
-            const Y_VALUE: i32 = -1;
+        /// Test that shrinking and growing the last allocation updates the
+        /// poisoned memory
+        #[test]
+        fn resize_last() {
+            let arena = FixedArena::with_capacity(64, 8);
+            let values = arena.alloc_array(0_u64, 2).unwrap();
+            let pointer = NonNull::from(values).cast::<u8>();
+            unsafe { arena.shrink_last(pointer, 16, 8).unwrap() };
+            assert!(is_poisoned(&arena, 8));
+            arena.try_grow_last(pointer, 8, 24, 8).unwrap();
+            assert!(!is_poisoned(&arena, 16));
+            assert!(is_poisoned(&arena, 24));
+        }
 
-            for element in array.iter_mut() {
-                element.x = X_VALUE;
-                element.y = Y_VALUE;
+        /// Test that a caller provided buffer is unpoisoned when the arena is
+        /// dropped
+        #[test]
+        fn from_buffer() {
+            let mut buffer = [MaybeUninit::<u8>::uninit(); 32];
+            {
+                let arena = FixedArena::from_buffer(&mut buffer);
+                arena.alloc(1_u64).unwrap();
+                assert!(is_poisoned(&arena, 8));
             }
-
-            verify_i32_struct_array(array, 1, -1);
+            buffer[31].write(1);
         }
+    }
 
-        /// Common code for testing allocating an array to capacity
-        fn alloc_array_to_capacity_common(
-            test_array: &mut [I32Struct],
-            capacity: usize,
-        ) {
-            assert_eq!(capacity % size_of::<I32Struct>(), 0);
+    mod alignment {
+        use super::*;
 
-            const X_VALUE: i32 = 1;
-            const Y_VALUE: i32 = -1;
+        /// Check that a reference is aligned for its type
+        fn is_aligned<T>(value: &T) -> bool {
+            (value as *const T).is_aligned()
+        }
 
-            for element in test_array.iter_mut() {
-                element.x = X_VALUE;
-                element.y = Y_VALUE;
-            }
+        /// Test that a larger type after a small one is aligned
+        #[test]
+        fn alloc_after_small_type() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            arena.alloc(SmallStruct { x: 1, y: 2 }).unwrap();
+            let larger = arena.alloc(LargerStruct { x: 3, y: 4 }).unwrap();
+            assert!(is_aligned(larger));
+            assert_eq!(larger.x, 3);
+            assert_eq!(larger.y, 4);
+        }
 
-            verify_i32_struct_array(test_array, X_VALUE, Y_VALUE);
+        /// Test alignments larger than the arena's alignment
+        #[test]
+        fn alloc_over_aligned() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            arena.alloc(SmallStruct { x: 1, y: 2 }).unwrap();
+            let over_aligned =
+                arena.alloc_zeroed::<OverAlignedStruct>().unwrap();
+            assert!(is_aligned(over_aligned));
+            assert_eq!(over_aligned.x, 0);
         }
 
-        /// Common code for testing allocating multiple arrays
-        /// This code will mutate the data, check the results, and also make
-        /// sure there isn't unexpected memory overlap between the two arrays
-        fn alloc_multiple_arrays_common(
-            test_array_one: &mut [I32Struct],
-            test_array_two: &mut [I32Struct],
-        ) {
-            const ARRAY_ONE_X_VALUE: i32 = 0x7FFFFFFF;
-            const ARRAY_ONE_Y_VALUE: i32 = -1;
+        /// Test that every array allocation method aligns its elements
+        #[test]
+        fn alloc_arrays_aligned() {
+            let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+            arena.alloc(ThreeByteStruct { x: 1, y: 2, z: 3 }).unwrap();
+            let array =
+                arena.alloc_array(LargerStruct { x: 1, y: 2 }, 4).unwrap();
+            assert!(is_aligned(&array[0]));
 
-            const ARRAY_TWO_X_VALUE: i32 = 0x7ABABABA;
-            const ARRAY_TWO_Y_VALUE: i32 = -1 * 0x7ABABABA;
+            arena.alloc(ThreeByteStruct { x: 1, y: 2, z: 3 }).unwrap();
+            let array = arena.alloc_zeroed_array::<LargerStruct>(4).unwrap();
+            assert!(is_aligned(&array[0]));
 
-            for test_value in test_array_one.iter_mut() {
-                test_value.x = ARRAY_ONE_X_VALUE;
-                test_value.y = ARRAY_ONE_Y_VALUE;
-            }
-            for test_value in test_array_two.iter_mut() {
-                test_value.x = ARRAY_TWO_X_VALUE;
-                test_value.y = ARRAY_TWO_Y_VALUE;
-            }
+            arena.alloc(ThreeByteStruct { x: 1, y: 2, z: 3 }).unwrap();
+            let array =
+                unsafe { arena.alloc_uninitialized_array::<LargerStruct>(4) }
+                    .unwrap();
+            assert!(is_aligned(&array[0]));
 
-            for test_value in test_array_one.iter() {
-                assert_eq!(test_value.x, ARRAY_ONE_X_VALUE);
-                assert_eq!(test_value.y, ARRAY_ONE_Y_VALUE);
-            }
-            for test_value in test_array_two.iter() {
-                assert_eq!(test_value.x, ARRAY_TWO_X_VALUE);
-                assert_eq!(test_value.y, ARRAY_TWO_Y_VALUE);
-            }
+            arena.alloc(ThreeByteStruct { x: 1, y: 2, z: 3 }).unwrap();
+            let array =
+                arena.alloc_zeroed_array::<OverAlignedStruct>(2).unwrap();
+            assert!(is_aligned(&array[0]));
+        }
 
-            // make sure array_one can't overwrite array_two
-            for test_value in test_array_one.iter_mut() {
-                test_value.x = ARRAY_ONE_X_VALUE;
-                test_value.y = ARRAY_ONE_Y_VALUE;
-            }
+        /// Test that padding is tracked and cleared by reset
+        #[test]
+        fn padding_tracked() {
+            let mut arena = FixedArena::with_capacity(1024, 8);
+            assert_eq!(arena.padding(), 0);
+            arena.alloc(ThreeByteStruct { x: 1, y: 2, z: 3 }).unwrap();
+            assert_eq!(arena.padding(), 0);
+            arena.alloc(LargerStruct { x: 1, y: 2 }).unwrap();
+            assert_eq!(arena.padding(), 5);
+            assert_eq!(arena.used.get(), 24);
 
-            for test_value in test_array_one.iter() {
-                assert_eq!(test_value.x, ARRAY_ONE_X_VALUE);
-                assert_eq!(test_value.y, ARRAY_ONE_Y_VALUE);
-            }
-            for test_value in test_array_two.iter() {
-                assert_eq!(test_value.x, ARRAY_TWO_X_VALUE);
-                assert_eq!(test_value.y, ARRAY_TWO_Y_VALUE);
-            }
+            arena.reset();
+            assert_eq!(arena.padding(), 0);
         }
 
-        /// Common code for testing allocating an array over capacity
-        fn alloc_array_over_capacity_common<T>(result: Result<T, AllocError>) {
-            match result {
-                Ok(_) => assert!(false),
-                Err(err) => assert_eq!(err, AllocError::AtCapacity),
+        /// Test that padding counts against the capacity of the arena
+        #[test]
+        fn padding_at_capacity() {
+            let arena = FixedArena::with_capacity(16, 8);
+            arena.alloc(1_u8).unwrap();
+            arena.alloc(1_u64).unwrap();
+            match arena.alloc(1_u8) {
+                Ok(_) => panic!("allocation should fail"),
+                Err(err) => {
+                    assert!(matches!(err, AllocError::AtCapacity { .. }))
+                }
             };
         }
-
-        /// Common code for verifying an i32 structure
-        fn verify_i32_struct_array(
-            array: &[I32Struct],
-            expected_x_value: i32,
-            expected_y_value: i32,
-        ) {
-            for element in array {
-                assert_eq!(element.x, expected_x_value);
-                assert_eq!(element.y, expected_y_value);
-            }
-        }
     }
 
     mod benchmark {
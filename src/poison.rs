@@ -0,0 +1,19 @@
+//! Byte patterns written over arena memory by the `debug-poison` feature, so
+//! reads of freed or uninitialized memory show up as recognizable garbage
+
+use core::ptr;
+
+/// Written over memory the arena gets back, by `reset`, `rollback`,
+/// `shrink_last`, `pop_last` and dropped scopes, vectors and strings
+pub const FREED: u8 = 0xDD;
+
+/// Written over memory from `alloc_uninit`, `alloc_uninit_slice` and
+/// `alloc_uninitialized_array`
+pub const UNINIT: u8 = 0xCD;
+
+/// Fill the `len` bytes at `pointer` with `pattern`
+/// # Safety
+/// The bytes must be valid for writes and not be in use
+pub(crate) unsafe fn fill(pointer: *mut u8, len: usize, pattern: u8) {
+    unsafe { ptr::write_bytes(pointer, pattern, len) }
+}
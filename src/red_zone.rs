@@ -0,0 +1,50 @@
+//! Canary bytes placed after every allocation of a `FixedArena` with red
+//! zones enabled, so that `FixedArena::verify` can find allocations that were
+//! written past their end. Needs the `red-zone` feature.
+
+use core::fmt;
+
+/// The byte that fills the canary after each allocation
+pub const CANARY: u8 = 0xFA;
+
+/// The number of canary bytes after each allocation
+pub const CANARY_SIZE: usize = 8;
+
+/// The record of an allocation made with red zones enabled. Records are
+/// stored in the arena right before the allocation they describe, and are
+/// linked from the most recent record to the oldest.
+/// An overrun of the previous allocation that gets past its canary writes
+/// over the record from the front, so `type_name` is last and only read once
+/// the other fields have been checked.
+#[repr(C)]
+pub(crate) struct RedZone {
+    /// The offset of the allocation from the start of the arena
+    pub(crate) offset: usize,
+    /// The size in bytes of the allocation, not including the canary
+    pub(crate) size: usize,
+    pub(crate) next: *mut RedZone,
+    pub(crate) type_name: &'static str,
+}
+
+/// An allocation whose canary was overwritten, from `FixedArena::verify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overrun {
+    /// The offset of the allocation from the start of the arena
+    pub offset: usize,
+    /// The size in bytes of the allocation
+    pub size: usize,
+    /// The name of the type that was allocated
+    pub type_name: &'static str,
+}
+
+impl fmt::Display for Overrun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "canary overwritten after the {} byte allocation of {} at offset {}",
+            self.size, self.type_name, self.offset
+        )
+    }
+}
+
+impl core::error::Error for Overrun {}
@@ -0,0 +1,243 @@
+use core::ops::Deref;
+
+use crate::FixedArena;
+
+/// A mark of how much of an arena was used at some point. Returned by
+/// `FixedArena::checkpoint` and consumed by `FixedArena::rollback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub(crate) epoch: usize,
+    pub(crate) used: usize,
+    pub(crate) padding: usize,
+}
+
+/// A guard that rolls its arena back to the point where the scope was
+/// created when it is dropped.
+/// Allocations made through the scope borrow the scope, so they can't be used
+/// after the memory they live in has been released. The scope mutably
+/// borrows the arena, so the arena can't be used directly until the scope
+/// ends.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::FixedArena;
+/// let mut arena = FixedArena::with_capacity(4096, 4);
+/// {
+///     let scope = arena.scope();
+///     let scratch = scope.alloc_zeroed_array::<i32>(256).unwrap();
+///     scratch[0] = 1;
+/// }
+/// assert_eq!(arena.alloc_zeroed_array::<i32>(1024).unwrap().len(), 1024);
+/// ```
+/// Allocations can't outlive the scope that made them
+/// ```compile_fail
+/// # use tea_fixed_arena::FixedArena;
+/// let mut arena = FixedArena::with_capacity(4096, 4);
+/// let value;
+/// {
+///     let scope = arena.scope();
+///     value = scope.alloc(5).unwrap();
+/// }
+/// assert_eq!(*value, 5);
+/// ```
+pub struct ArenaScope<'a, 'b> {
+    arena: &'a mut FixedArena<'b>,
+    checkpoint: Checkpoint,
+}
+
+impl<'a, 'b> ArenaScope<'a, 'b> {
+    /// Make a new scope over an arena, starting at the current point
+    pub(crate) fn new(arena: &'a mut FixedArena<'b>) -> ArenaScope<'a, 'b> {
+        let checkpoint = arena.checkpoint();
+        ArenaScope { arena, checkpoint }
+    }
+
+    /// Make a nested scope. Dropping the nested scope releases only what was
+    /// allocated through the nested scope.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::FixedArena;
+    /// let mut arena = FixedArena::with_capacity(4096, 4);
+    /// let mut outer = arena.scope();
+    /// {
+    ///     let inner = outer.scope();
+    ///     inner.alloc(1).unwrap();
+    /// }
+    /// let value = outer.alloc(2).unwrap();
+    /// assert_eq!(*value, 2);
+    /// ```
+    pub fn scope(&mut self) -> ArenaScope<'_, 'b> {
+        ArenaScope::new(self.arena)
+    }
+}
+
+impl<'b> Deref for ArenaScope<'_, 'b> {
+    type Target = FixedArena<'b>;
+
+    fn deref(&self) -> &FixedArena<'b> {
+        self.arena
+    }
+}
+
+impl Drop for ArenaScope<'_, '_> {
+    /// Rolls the arena back to where it was when the scope was created
+    fn drop(&mut self) {
+        self.arena.rollback(self.checkpoint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    use crate::test_common::{I32Struct, TestStruct};
+
+    const DEFAULT_ALIGN: usize = 4;
+
+    /// Test that rolling back releases only what was allocated after the
+    /// checkpoint
+    #[test]
+    fn rollback() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+        let checkpoint = arena.checkpoint();
+        let used = arena.used.get();
+
+        arena.alloc_zeroed_array::<I32Struct>(16).unwrap();
+        assert!(arena.used.get() > used);
+
+        arena.rollback(checkpoint);
+        assert_eq!(arena.used.get(), used);
+    }
+
+    /// Test that padding is restored by a rollback
+    #[test]
+    fn rollback_padding() {
+        let mut arena = FixedArena::with_capacity(1024, 8);
+        arena.alloc(1_u8).unwrap();
+        let checkpoint = arena.checkpoint();
+        arena.alloc(1_u64).unwrap();
+        assert_eq!(arena.padding(), 7);
+
+        arena.rollback(checkpoint);
+        assert_eq!(arena.padding(), 0);
+    }
+
+    /// Test that a checkpoint the arena has already gone below does nothing
+    #[test]
+    fn rollback_stale_checkpoint() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let first = arena.checkpoint();
+        arena.alloc(1_u32).unwrap();
+        let second = arena.checkpoint();
+
+        arena.rollback(first);
+        arena.rollback(second);
+        assert_eq!(arena.used.get(), 0);
+    }
+
+    /// Test that a checkpoint from before a reset does nothing, even once the
+    /// arena has allocated past it again
+    #[test]
+    fn rollback_after_reset() {
+        let mut arena = FixedArena::with_capacity(1024, 8);
+        arena.alloc(1_u8).unwrap();
+        let checkpoint = arena.checkpoint();
+        arena.reset();
+
+        arena.alloc(1_u64).unwrap();
+        arena.alloc(2_u64).unwrap();
+        arena.rollback(checkpoint);
+        assert_eq!(arena.used.get(), 16);
+        assert_eq!(arena.padding(), 0);
+    }
+
+    /// Test that a checkpoint from another arena does nothing
+    #[test]
+    fn rollback_other_arena() {
+        let mut arena = FixedArena::with_capacity(1024, 8);
+        let other = FixedArena::with_capacity(1024, 8);
+        let checkpoint = other.checkpoint();
+        arena.alloc(1_u64).unwrap();
+
+        arena.rollback(checkpoint);
+        assert_eq!(arena.used.get(), 8);
+    }
+
+    /// Test that rolling back to a checkpoint the arena went below and then
+    /// allocated past again doesn't restore its older padding
+    #[test]
+    fn rollback_passed_checkpoint() {
+        let mut arena = FixedArena::with_capacity(1024, 8);
+        let first = arena.checkpoint();
+        arena.alloc(1_u8).unwrap();
+        arena.alloc(1_u64).unwrap();
+        let second = arena.checkpoint();
+        assert_eq!(arena.padding(), 7);
+
+        arena.rollback(first);
+        arena.alloc_array(1_u64, 4).unwrap();
+        arena.rollback(second);
+        assert_eq!(arena.used.get(), 16);
+        assert_eq!(arena.padding(), 0);
+    }
+
+    /// Test that destructors of values allocated after a checkpoint run on
+    /// rollback, and the others don't
+    #[test]
+    fn rollback_runs_drops() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut arena = FixedArena::with_capacity(1024, 8);
+
+        struct DropLogger(u32, Arc<Mutex<Vec<u32>>>);
+        impl Drop for DropLogger {
+            fn drop(&mut self) {
+                self.1.lock().unwrap().push(self.0);
+            }
+        }
+
+        arena.alloc_with_drop(DropLogger(0, log.clone())).unwrap();
+        let checkpoint = arena.checkpoint();
+        arena.alloc_with_drop(DropLogger(1, log.clone())).unwrap();
+        arena.alloc_with_drop(DropLogger(2, log.clone())).unwrap();
+
+        arena.rollback(checkpoint);
+        assert_eq!(*log.lock().unwrap(), vec![2, 1]);
+
+        arena.alloc_with_drop(DropLogger(3, log.clone())).unwrap();
+        arena.reset();
+        assert_eq!(*log.lock().unwrap(), vec![2, 1, 3, 0]);
+    }
+
+    /// Test that dropping a scope rolls back the arena
+    #[test]
+    fn scope() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc(1_u32).unwrap();
+        {
+            let scope = arena.scope();
+            let values =
+                scope.alloc_array(I32Struct { x: 1, y: 2 }, 8).unwrap();
+            assert_eq!(values[7].y, 2);
+            assert_eq!(scope.used.get(), 4 + 8 * 8);
+        }
+        assert_eq!(arena.used.get(), 4);
+    }
+
+    /// Test that nested scopes only roll back their own allocations
+    #[test]
+    fn nested_scope() {
+        let mut arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        {
+            let mut outer = arena.scope();
+            outer.alloc(1_u32).unwrap();
+            {
+                let inner = outer.scope();
+                inner.alloc(2_u32).unwrap();
+                assert_eq!(inner.used.get(), 8);
+            }
+            assert_eq!(outer.used.get(), 4);
+        }
+        assert_eq!(arena.used.get(), 0);
+    }
+}
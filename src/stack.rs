@@ -0,0 +1,455 @@
+use core::{
+    cell::UnsafeCell,
+    hint,
+    mem::MaybeUninit,
+    ptr, slice,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use crate::{asan, errors::AllocError, zeroable::Zeroable, FixedArena};
+
+/// An arena that stores its `N` bytes inline instead of on the heap, for
+/// short-lived scratch space on the stack. Its allocation methods are those
+/// of a `FixedArena` over the inline bytes. Allocations borrow the arena, so
+/// it can't be moved while they are in use.
+/// Under AddressSanitizer, the bytes after the allocations are poisoned like
+/// those of a `FixedArena`, so moving an arena that has been allocated from
+/// is reported, since the move copies them.
+/// `new` is a `const fn`, so the arena can be a `static` item. It is `Sync`:
+/// each allocation takes a spin lock for as long as it bumps the arena,
+/// which costs one uncontended atomic operation on a single thread. An
+/// `AtomicFixedArena` avoids the lock for a `static` that many threads
+/// allocate from at once.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::stack::StackArena;
+/// let mut arena = StackArena::<256>::new();
+/// let values = arena.alloc_array(1_u32, 16).unwrap();
+/// assert_eq!(values.len(), 16);
+/// arena.reset();
+/// ```
+/// ```
+/// # use tea_fixed_arena::stack::StackArena;
+/// static SCRATCH: StackArena<1024> = StackArena::new();
+///
+/// let value = SCRATCH.alloc(5).unwrap();
+/// assert_eq!(*value, 5);
+/// ```
+pub struct StackArena<const N: usize> {
+    bytes: UnsafeCell<[MaybeUninit<u8>; N]>,
+    /// Allocates from `bytes`. It is pointed at them before every use, since
+    /// the stack arena may have been moved since the last one. Only used
+    /// while `locked` is held, or through `&mut self`.
+    arena: FixedArena<'static>,
+    /// Held while a thread uses `arena`
+    locked: AtomicBool,
+}
+
+// Safety: `arena` is only used by the thread that holds `locked`, and the
+// lock orders its uses. The allocations it hands out don't overlap, and each
+// is only reachable through the reference returned to the thread that made
+// it.
+unsafe impl<const N: usize> Sync for StackArena<N> {}
+
+/// Unlocks a stack arena when it is dropped, even if an allocation panics
+struct Unlock<'s> {
+    locked: &'s AtomicBool,
+}
+
+impl Drop for Unlock<'_> {
+    fn drop(&mut self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<const N: usize> StackArena<N> {
+    /// Make a new, empty stack arena with a capacity of `N` bytes
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let arena = StackArena::<4096>::new();
+    /// ```
+    pub const fn new() -> StackArena<N> {
+        StackArena {
+            bytes: UnsafeCell::new([MaybeUninit::uninit(); N]),
+            arena: FixedArena::unplaced(N),
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// Wait until no other thread is using the arena, and point it at the
+    /// inline bytes. `arena` can be used until the returned guard is dropped.
+    fn lock(&self) -> Unlock<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(
+                false,
+                true,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .is_err()
+        {
+            hint::spin_loop();
+        }
+        let unlock = Unlock {
+            locked: &self.locked,
+        };
+        self.place();
+        unlock
+    }
+
+    /// Point the arena at the inline bytes, wherever the stack arena is now
+    fn place(&self) {
+        let base = self.bytes.get() as *mut u8;
+        let old = self.arena.base.replace(base);
+        if old != base {
+            // The bytes poisoned at the old place are stack memory again
+            if !old.is_null() {
+                asan::unpoison(old, N);
+            }
+            // and the bytes after the allocations at the new place are not
+            let used = self.arena.used();
+            asan::poison(unsafe { base.add(used) }, N - used);
+        }
+    }
+
+    /// Allocate and initialize a single instance of a data structure.
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let arena = StackArena::<64>::new();
+    /// let result = arena.alloc(5).unwrap();
+    /// assert_eq!(*result, 5);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc<T>(&self, val: T) -> Result<&mut T, AllocError> {
+        let _unlock = self.lock();
+        self.arena.alloc(val)
+    }
+
+    /// Allocate and initialize a single instance of a data structure. It is
+    /// initialized with a value of 0
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let arena = StackArena::<64>::new();
+    /// let result = arena.alloc_zeroed::<i32>().unwrap();
+    /// assert_eq!(*result, 0);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_zeroed<T>(&self) -> Result<&mut T, AllocError>
+    where
+        T: Zeroable,
+    {
+        let _unlock = self.lock();
+        self.arena.alloc_zeroed()
+    }
+
+    /// Allocates an array of type T with count elements. The initial value of
+    /// the elements in the array is val.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `val` - the value to initialize the elements in the array to
+    /// * `count` - the number of elements to allocate for the array
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let arena = StackArena::<64>::new();
+    /// let result = arena.alloc_array(1, 5).unwrap();
+    /// assert_eq!(result, [1, 1, 1, 1, 1]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_array<T>(
+        &self,
+        val: T,
+        count: usize,
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Clone,
+    {
+        // The clones are made after unlocking, in case `clone` allocates
+        // from the arena too
+        let values = {
+            let _unlock = self.lock();
+            self.arena.alloc_uninit_slice::<T>(count)?
+        };
+        unsafe {
+            let pointer = values.as_mut_ptr() as *mut T;
+            for index in 0..count {
+                ptr::write(pointer.add(index), val.clone());
+            }
+            Ok(slice::from_raw_parts_mut(pointer, count))
+        }
+    }
+
+    /// Allocates an array of type `T` with count elements. The initial value of
+    /// the elements in the array is 0.
+    /// Returns `AllocError::LayoutOverflow` if the size of the array does not
+    /// fit in an `isize`
+    /// # Arguments
+    /// * `T` - Generic. The type to allocate
+    /// * `count` - the number of elements to allocate for the array
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let arena = StackArena::<64>::new();
+    /// let result = arena.alloc_zeroed_array::<i32>(5).unwrap();
+    /// assert_eq!(result, [0, 0, 0, 0, 0]);
+    /// ```
+    #[allow(clippy::mut_from_ref)]
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc_zeroed_array<T>(
+        &self,
+        count: usize,
+    ) -> Result<&mut [T], AllocError>
+    where
+        T: Zeroable,
+    {
+        let _unlock = self.lock();
+        self.arena.alloc_zeroed_array(count)
+    }
+
+    /// Resets the arena. The `used` value is set to 0, and any data allocated
+    /// since the last reset cannot be used. Like `FixedArena::reset`, this
+    /// mutably borrows self, so no earlier allocation can outlive it.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::stack::StackArena;
+    /// let mut arena = StackArena::<64>::new();
+    /// arena.alloc_zeroed_array::<i32>(16).unwrap();
+    /// arena.reset();
+    /// arena.alloc_zeroed_array::<i32>(16).unwrap();
+    /// ```
+    pub fn reset(&mut self) {
+        self.place();
+        self.arena.reset();
+    }
+
+    /// The number of bytes that have been skipped to satisfy the alignment of
+    /// allocations since the last reset
+    pub fn padding(&self) -> usize {
+        let _unlock = self.lock();
+        self.arena.padding()
+    }
+}
+
+impl<const N: usize> Default for StackArena<N> {
+    fn default() -> StackArena<N> {
+        StackArena::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test_common::{
+        I32Struct, LargerStruct, SmallStruct, TestStruct,
+    };
+
+    /// Test allocating single values
+    #[test]
+    fn alloc() {
+        let arena = StackArena::<64>::new();
+        let test = arena.alloc(TestStruct { x: 1.0, y: 2.0 }).unwrap();
+        let zeroed = arena.alloc_zeroed::<I32Struct>().unwrap();
+        assert!(test.x == 1.0);
+        assert!(test.y == 2.0);
+        assert_eq!(*zeroed, I32Struct { x: 0, y: 0 });
+    }
+
+    /// Test allocating arrays
+    #[test]
+    fn alloc_arrays() {
+        let arena = StackArena::<256>::new();
+        let first = arena.alloc_array(I32Struct { x: 1, y: -1 }, 8).unwrap();
+        let second = arena.alloc_zeroed_array::<I32Struct>(8).unwrap();
+        for element in first.iter_mut() {
+            element.x = 2;
+        }
+        assert!(first.iter().all(|e| *e == I32Struct { x: 2, y: -1 }));
+        assert!(second.iter().all(|e| *e == I32Struct { x: 0, y: 0 }));
+    }
+
+    /// Test that allocations are aligned
+    #[test]
+    fn alignment() {
+        let arena = StackArena::<64>::new();
+        arena.alloc(SmallStruct { x: 1, y: 2 }).unwrap();
+        let larger = arena.alloc(LargerStruct { x: 3, y: 4 }).unwrap();
+        assert!((larger as *const LargerStruct).is_aligned());
+    }
+
+    /// Test filling the arena, failing, resetting and allocating again
+    #[test]
+    fn at_capacity_reset() {
+        const CAPACITY: usize = 128;
+        let mut arena = StackArena::<CAPACITY>::new();
+        arena.alloc_zeroed_array::<u8>(CAPACITY).unwrap();
+        match arena.alloc_zeroed::<u8>() {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert!(matches!(err, AllocError::AtCapacity { .. })),
+        };
+
+        arena.reset();
+        assert_eq!(arena.arena.used(), 0);
+        arena.alloc_zeroed_array::<u8>(CAPACITY).unwrap();
+    }
+
+    /// Test that a zero sized arena only fails allocations
+    #[test]
+    fn zero_sized() {
+        let arena = StackArena::<0>::new();
+        assert!(arena.alloc(1_u8).is_err());
+    }
+
+    /// Test that the arena keeps its values and allocates from its new
+    /// place after a move. AddressSanitizer reports the move, which copies
+    /// the poisoned bytes after the allocations.
+    #[cfg(not(sanitize = "address"))]
+    #[test]
+    fn moved() {
+        let arena = StackArena::<64>::new();
+        arena.alloc(1_u64).unwrap();
+        let moved = Box::new(arena);
+        let value = moved.alloc(2_u64).unwrap() as *mut u64;
+        let bytes = moved.bytes.get() as *mut u64;
+        assert_eq!(value, bytes.wrapping_add(1));
+        assert_eq!(unsafe { bytes.read() }, 1);
+        assert_eq!(moved.arena.used(), 16);
+    }
+
+    /// Test that the arena can be made in a constant initializer
+    #[test]
+    fn const_new() {
+        thread_local! {
+            static SCRATCH: StackArena<16> = const { StackArena::new() };
+        }
+        SCRATCH.with(|arena| assert_eq!(*arena.alloc(3_u8).unwrap(), 3));
+    }
+
+    /// Test that threads can allocate from a static arena at the same time
+    /// without overlapping
+    #[test]
+    fn static_threads() {
+        static SCRATCH: StackArena<1024> = StackArena::new();
+        let threads: Vec<_> = (0..4_u64)
+            .map(|thread| {
+                std::thread::spawn(move || {
+                    let values: Vec<&mut u64> = (0..8)
+                        .map(|value| SCRATCH.alloc(thread * 8 + value).unwrap())
+                        .collect();
+                    let array = SCRATCH.alloc_array(thread, 4).unwrap();
+                    (values, array)
+                })
+            })
+            .collect();
+        for (thread, handle) in threads.into_iter().enumerate() {
+            let (values, array) = handle.join().unwrap();
+            let thread = thread as u64;
+            for (value, expected) in values.iter().zip(thread * 8..) {
+                assert_eq!(**value, expected);
+            }
+            assert_eq!(array, [thread; 4]);
+        }
+        assert_eq!(SCRATCH.padding(), 0);
+    }
+
+    /// Test that `alloc_array` doesn't hold the lock while cloning, so a
+    /// clone can allocate from the same arena
+    #[test]
+    fn clone_allocates() {
+        static SCRATCH: StackArena<256> = StackArena::new();
+        struct Allocates;
+        impl Clone for Allocates {
+            fn clone(&self) -> Allocates {
+                SCRATCH.alloc(0_u8).unwrap();
+                Allocates
+            }
+        }
+        let values = SCRATCH.alloc_array(Allocates, 4).unwrap();
+        assert_eq!(values.len(), 4);
+    }
+
+    /// Test that reset fills the released memory with the poison pattern.
+    /// AddressSanitizer doesn't allow reading the released memory.
+    #[cfg(all(feature = "debug-poison", not(sanitize = "address")))]
+    #[test]
+    fn reset_poison() {
+        let mut arena = StackArena::<16>::new();
+        arena.alloc(1_u64).unwrap();
+        arena.reset();
+        let bytes = unsafe { (arena.bytes.get() as *const [u8; 8]).read() };
+        assert_eq!(bytes, [crate::poison::FREED; 8]);
+    }
+
+    #[cfg(sanitize = "address")]
+    mod asan {
+        use super::*;
+
+        unsafe extern "C" {
+            fn __asan_address_is_poisoned(
+                addr: *const core::ffi::c_void,
+            ) -> core::ffi::c_int;
+        }
+
+        /// Check whether the byte at `offset` in the arena is poisoned
+        fn is_poisoned<const N: usize>(
+            arena: &StackArena<N>,
+            offset: usize,
+        ) -> bool {
+            let pointer =
+                unsafe { (arena.bytes.get() as *const u8).add(offset) };
+            unsafe { __asan_address_is_poisoned(pointer.cast()) != 0 }
+        }
+
+        /// Test that only allocated memory is unpoisoned, from the first
+        /// allocation on
+        #[test]
+        fn alloc() {
+            let arena = StackArena::<64>::new();
+            arena.alloc(1_u64).unwrap();
+            assert!(!is_poisoned(&arena, 7));
+            assert!(is_poisoned(&arena, 8));
+            assert!(is_poisoned(&arena, 63));
+            arena.alloc(2_u64).unwrap();
+            assert!(!is_poisoned(&arena, 15));
+            assert!(is_poisoned(&arena, 16));
+        }
+
+        /// Test that the memory after the allocations is poisoned at the
+        /// new place of a moved arena, and not at the old one
+        #[test]
+        fn moved() {
+            let arena = StackArena::<64>::new();
+            arena.alloc(1_u64).unwrap();
+            // A move would copy the poisoned bytes, so only the allocated
+            // ones are copied to another arena left as if it had been moved
+            let moved = StackArena::<64>::new();
+            unsafe {
+                ptr::copy_nonoverlapping(
+                    arena.bytes.get() as *const u8,
+                    moved.bytes.get() as *mut u8,
+                    8,
+                )
+            };
+            moved.arena.base.set(arena.arena.base.get());
+            moved.arena.used.set(8);
+
+            moved.alloc(2_u64).unwrap();
+            assert!(!is_poisoned(&moved, 15));
+            assert!(is_poisoned(&moved, 16));
+            assert!(is_poisoned(&moved, 63));
+            assert!(!is_poisoned(&arena, 16));
+        }
+    }
+}
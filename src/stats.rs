@@ -0,0 +1,28 @@
+/// Usage statistics of a `FixedArena` over its whole lifetime, from
+/// `FixedArena::stats`. Needs the `stats` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ArenaStats {
+    /// The largest number of bytes that were in use at once, across resets
+    pub high_water_mark: usize,
+    /// The number of successful allocations
+    pub allocations: usize,
+    /// The number of allocations that returned an error
+    pub failed_allocations: usize,
+    /// The number of bytes skipped to align allocations
+    pub padding: usize,
+    /// The number of times the arena was reset
+    pub resets: usize,
+}
+
+impl ArenaStats {
+    /// Stats with every count at 0, for constant initializers
+    pub(crate) const fn new() -> ArenaStats {
+        ArenaStats {
+            high_water_mark: 0,
+            allocations: 0,
+            failed_allocations: 0,
+            padding: 0,
+            resets: 0,
+        }
+    }
+}
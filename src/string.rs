@@ -0,0 +1,408 @@
+use core::{
+    fmt,
+    ops::{Deref, DerefMut},
+    str,
+};
+
+#[cfg(feature = "track-callers")]
+use core::panic::Location;
+
+#[cfg(feature = "track-callers")]
+use crate::callers::Caller;
+use crate::{errors::AllocError, vec::ArenaVec, FixedArena};
+
+/// A growable string whose bytes are stored in a `FixedArena`. It grows like
+/// `ArenaVec`, and running out of space returns an `AllocError`.
+/// It can be written to with `write!`, and implements `fmt::Write`. Use
+/// `format_in!` to format straight into a new string.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{string::ArenaString, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let mut message = ArenaString::new_in(&arena);
+/// message.push_str("frame ").unwrap();
+/// write!(message, "{}", 42).unwrap();
+/// assert_eq!(message, "frame 42");
+/// ```
+pub struct ArenaString<'a> {
+    bytes: ArenaVec<'a, u8>,
+}
+
+impl<'a> ArenaString<'a> {
+    /// Make a new, empty string. Nothing is allocated until text is added.
+    /// # Arguments
+    /// * `arena` - The arena to store the string in
+    pub fn new_in(arena: &'a FixedArena<'a>) -> ArenaString<'a> {
+        ArenaString {
+            bytes: ArenaVec::new_in(arena),
+        }
+    }
+
+    /// Make a new, empty string with space for `capacity` bytes
+    /// # Arguments
+    /// * `capacity` - The number of bytes to make space for
+    /// * `arena` - The arena to store the string in
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn with_capacity_in(
+        capacity: usize,
+        arena: &'a FixedArena<'a>,
+    ) -> Result<ArenaString<'a>, AllocError> {
+        Ok(ArenaString {
+            bytes: ArenaVec::with_capacity_in(capacity, arena)?,
+        })
+    }
+
+    /// Make a new string with a copy of `val`
+    /// # Arguments
+    /// * `val` - The text to copy
+    /// * `arena` - The arena to store the string in
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn from_str_in(
+        val: &str,
+        arena: &'a FixedArena<'a>,
+    ) -> Result<ArenaString<'a>, AllocError> {
+        let mut result = ArenaString::with_capacity_in(val.len(), arena)?;
+        result.push_str(val)?;
+        Ok(result)
+    }
+
+    /// The length of the string in bytes
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The number of bytes the string can hold without growing
+    pub fn capacity(&self) -> usize {
+        self.bytes.capacity()
+    }
+
+    /// Add text to the end of the string
+    /// # Arguments
+    /// * `val` - The text to add
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn push_str(&mut self, val: &str) -> Result<(), AllocError> {
+        self.bytes.extend_from_slice(val.as_bytes())
+    }
+
+    /// Add a character to the end of the string
+    /// # Arguments
+    /// * `val` - The character to add
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn push(&mut self, val: char) -> Result<(), AllocError> {
+        self.push_str(val.encode_utf8(&mut [0; 4]))
+    }
+
+    /// Format arguments onto the end of the string. This is what `write!`
+    /// calls, so with the `track-callers` feature the allocations count for
+    /// the code that used `write!` rather than for this crate.
+    /// # Arguments
+    /// * `args` - The arguments to format, from `format_args!`
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        #[cfg(feature = "track-callers")]
+        let _caller = self
+            .bytes
+            .arena()
+            .record_for(Caller::Location(Location::caller()));
+        fmt::Write::write_fmt(self, args)
+    }
+
+    /// Shorten the string to `len` bytes. Does nothing if the string is
+    /// already `len` bytes or shorter. Panics if `len` is not on a character
+    /// boundary.
+    /// # Arguments
+    /// * `len` - The number of bytes to keep
+    pub fn truncate(&mut self, len: usize) {
+        if len < self.len() {
+            assert!(self.is_char_boundary(len), "len is not a char boundary");
+            self.bytes.truncate(len);
+        }
+    }
+
+    /// Remove all of the text
+    pub fn clear(&mut self) {
+        self.bytes.clear();
+    }
+
+    /// The string as a string slice
+    pub fn as_str(&self) -> &str {
+        unsafe { str::from_utf8_unchecked(&self.bytes) }
+    }
+
+    /// The string as a mutable string slice
+    pub fn as_mut_str(&mut self) -> &mut str {
+        unsafe { str::from_utf8_unchecked_mut(&mut self.bytes) }
+    }
+
+    /// Turn the string into a string slice that lives as long as the arena
+    /// borrow
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{string::ArenaString, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let name: &str = ArenaString::from_str_in("arena", &arena)
+    ///     .unwrap()
+    ///     .into_str();
+    /// assert_eq!(name, "arena");
+    /// ```
+    pub fn into_str(self) -> &'a mut str {
+        unsafe { str::from_utf8_unchecked_mut(self.bytes.into_slice()) }
+    }
+}
+
+impl Deref for ArenaString<'_> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl DerefMut for ArenaString<'_> {
+    fn deref_mut(&mut self) -> &mut str {
+        self.as_mut_str()
+    }
+}
+
+impl fmt::Write for ArenaString<'_> {
+    /// Fails with `fmt::Error` when the arena is out of space
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s).map_err(|_| fmt::Error)
+    }
+}
+
+impl fmt::Display for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Debug for ArenaString<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl PartialEq<str> for ArenaString<'_> {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for ArenaString<'_> {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+/// Format arguments into a new string in an arena. Used by `format_in!`.
+/// Panics if a formatting trait implementation returns an error, like
+/// `format!` does.
+/// # Arguments
+/// * `arena` - The arena to store the string in
+/// * `args` - The arguments to format, from `format_args!`
+#[cfg_attr(feature = "track-callers", track_caller)]
+pub fn format<'a>(
+    arena: &'a FixedArena<'a>,
+    args: fmt::Arguments<'_>,
+) -> Result<ArenaString<'a>, AllocError> {
+    /// Keeps the allocation error that `fmt::Write` can't return
+    struct Writer<'s, 'a> {
+        string: &'s mut ArenaString<'a>,
+        error: Option<AllocError>,
+        /// Where `format_in!` was used, which the allocations count for
+        #[cfg(feature = "track-callers")]
+        caller: &'static Location<'static>,
+    }
+
+    impl fmt::Write for Writer<'_, '_> {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            #[cfg(feature = "track-callers")]
+            let _caller = self
+                .string
+                .bytes
+                .arena()
+                .record_for(Caller::Location(self.caller));
+            self.string.push_str(s).map_err(|error| {
+                self.error = Some(error);
+                fmt::Error
+            })
+        }
+    }
+
+    let mut string = ArenaString::new_in(arena);
+    let mut writer = Writer {
+        string: &mut string,
+        error: None,
+        #[cfg(feature = "track-callers")]
+        caller: Location::caller(),
+    };
+    if fmt::write(&mut writer, args).is_err() {
+        return match writer.error {
+            Some(error) => Err(error),
+            None => panic!(
+                "a formatting trait implementation returned an error when \
+                 the underlying stream did not"
+            ),
+        };
+    }
+    Ok(string)
+}
+
+/// Format a string into a `FixedArena`, like `format!`. Evaluates to a
+/// `Result<ArenaString, AllocError>`.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{format_in, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let request = 7;
+/// let message = format_in!(&arena, "request {} took {}ms", request, 12);
+/// assert_eq!(message.unwrap(), "request 7 took 12ms");
+/// ```
+#[macro_export]
+macro_rules! format_in {
+    ($arena:expr, $($arg:tt)*) => {
+        $crate::string::format($arena, ::core::format_args!($($arg)*))
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::TopCallers;
+
+    const DEFAULT_ALIGN: usize = 8;
+
+    /// Test copying string slices into the arena
+    #[test]
+    fn alloc_str() {
+        let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+        let first = arena.alloc_str("first").unwrap();
+        let second = arena.alloc_str("second ünïcode").unwrap();
+        first.make_ascii_uppercase();
+        assert_eq!(first, "FIRST");
+        assert_eq!(second, "second ünïcode");
+        assert_eq!(arena.alloc_str("").unwrap(), "");
+    }
+
+    /// Test that strings that don't fit return an error
+    #[test]
+    fn alloc_str_over_capacity() {
+        let arena = FixedArena::with_capacity(4, DEFAULT_ALIGN);
+        match arena.alloc_str("too long") {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert_eq!(
+                err,
+                AllocError::AtCapacity {
+                    requested: 8,
+                    align: 1,
+                    remaining: 4,
+                    top_callers: TopCallers::NONE,
+                }
+            ),
+        };
+    }
+
+    /// Test building a string piece by piece
+    #[test]
+    fn push() {
+        let arena = FixedArena::with_capacity(256, DEFAULT_ALIGN);
+        let mut string = ArenaString::new_in(&arena);
+        string.push_str("abc").unwrap();
+        string.push('d').unwrap();
+        string.push('é').unwrap();
+        assert_eq!(string, "abcdé");
+        assert_eq!(string.len(), 6);
+
+        string.truncate(3);
+        assert_eq!(string, "abc");
+        string.clear();
+        assert!(string.is_empty());
+    }
+
+    /// Test that truncating inside a character panics
+    #[test]
+    #[should_panic]
+    fn truncate_char_boundary() {
+        let arena = FixedArena::with_capacity(256, DEFAULT_ALIGN);
+        let mut string = ArenaString::from_str_in("é", &arena).unwrap();
+        string.truncate(1);
+    }
+
+    /// Test writing to a string with write!
+    #[test]
+    fn write() {
+        let arena = FixedArena::with_capacity(256, DEFAULT_ALIGN);
+        let mut string = ArenaString::new_in(&arena);
+        for index in 0..3 {
+            write!(string, "{},", index).unwrap();
+        }
+        assert_eq!(string, "0,1,2,");
+    }
+
+    /// Test that write! fails when the arena is out of space
+    #[test]
+    fn write_over_capacity() {
+        let arena = FixedArena::with_capacity(8, DEFAULT_ALIGN);
+        let mut string = ArenaString::new_in(&arena);
+        assert!(write!(string, "{}", 1234567890_u64).is_err());
+    }
+
+    /// Test formatting with the macro
+    #[test]
+    fn format_in() {
+        let arena = FixedArena::with_capacity(256, DEFAULT_ALIGN);
+        let name = "frame";
+        let first = format_in!(&arena, "{} {:>4}", name, 7).unwrap();
+        let second = format_in!(&arena, "{:?}", [1, 2]).unwrap();
+        assert_eq!(first, "frame    7");
+        assert_eq!(second, "[1, 2]");
+        assert_eq!(first.into_str(), "frame    7");
+    }
+
+    /// Test that format_in! returns the allocation error
+    #[test]
+    fn format_in_over_capacity() {
+        let arena = FixedArena::with_capacity(4, DEFAULT_ALIGN);
+        let result = format_in!(&arena, "{}", 123456789);
+        assert!(matches!(result, Err(AllocError::AtCapacity { .. })));
+    }
+
+    /// Test that a Display implementation that fails causes a panic
+    #[test]
+    #[should_panic]
+    fn format_in_display_error() {
+        struct Failing;
+        impl fmt::Display for Failing {
+            fn fmt(&self, _: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+        let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+        let _ = format_in!(&arena, "{}", Failing);
+    }
+
+    /// Test that allocations from formatting count for the line that used
+    /// `format_in!` or `write!`
+    #[cfg(feature = "track-callers")]
+    #[test]
+    fn format_callers() {
+        let arena = FixedArena::with_capacity(256, DEFAULT_ALIGN);
+        let format_line = line!() + 1;
+        let mut string = format_in!(&arena, "{} {}", "frame", 7).unwrap();
+        let write_line = line!() + 1;
+        write!(string, " took {}ms", 12).unwrap();
+
+        let report = arena.report();
+        let lines: Vec<_> = report
+            .iter()
+            .map(|site| (site.location.file(), site.location.line()))
+            .collect();
+        assert_eq!(lines, [(file!(), write_line), (file!(), format_line)]);
+    }
+}
@@ -1,36 +1,37 @@
 use std::cmp::PartialEq;
+use tea_fixed_arena_derive::Zeroable;
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct TestStruct {
     pub x: f32,
     pub y: f32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct LargerStruct {
     pub x: i64,
     pub y: i64,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct I32Struct {
     pub x: i32,
     pub y: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct SmallerStruct {
     pub x: i16,
     pub y: i16,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct SmallStruct {
     pub x: i8,
     pub y: i8,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct MixedStruct {
     pub a: i64,
     pub b: i32,
@@ -42,7 +43,7 @@ pub struct MixedStruct {
     pub h: u8,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
 pub struct ThreeByteStruct {
     pub x: u8,
     pub y: u8,
@@ -52,3 +53,9 @@ pub struct ThreeByteStruct {
 pub fn get_element_count() -> usize {
     4096
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Zeroable)]
+#[repr(align(64))]
+pub struct OverAlignedStruct {
+    pub x: u8,
+}
@@ -0,0 +1,386 @@
+#[cfg(any(feature = "std", test))]
+use core::alloc::Layout;
+use core::{
+    cell::Cell,
+    fmt,
+    marker::PhantomData,
+    mem::MaybeUninit,
+    ops::{Index, IndexMut},
+    ptr, slice,
+};
+
+use crate::{errors::AllocError, FixedArena};
+
+/// An arena that only holds values of type `T`. The values are stored one
+/// after another, so everything allocated since the last reset can be
+/// iterated over or indexed like a slice. The values are dropped when the
+/// arena is reset or dropped.
+/// Allocations return shared references, since `iter` can reach the same
+/// values. Use `iter_mut` or indexing on a mutable arena to change them.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::typed::TypedFixedArena;
+/// struct Node {
+///     value: u32,
+///     parent: Option<usize>,
+/// }
+///
+/// let mut nodes = TypedFixedArena::<Node>::with_capacity(64);
+/// nodes.alloc(Node { value: 1, parent: None }).unwrap();
+/// nodes.alloc(Node { value: 2, parent: Some(0) }).unwrap();
+/// nodes.alloc(Node { value: 3, parent: Some(0) }).unwrap();
+///
+/// for node in nodes.iter_mut() {
+///     node.value *= 10;
+/// }
+/// let total: u32 = nodes.iter().map(|node| node.value).sum();
+/// assert_eq!(total, 60);
+/// assert_eq!(nodes[nodes[2].parent.unwrap()].value, 10);
+/// ```
+pub struct TypedFixedArena<'a, T> {
+    arena: FixedArena<'a>,
+    /// The first value, or a dangling pointer if there are none
+    start: Cell<*mut T>,
+    len: Cell<usize>,
+    values: PhantomData<T>,
+}
+
+// The arena owns its values, so it can be sent to another thread when they
+// can be
+unsafe impl<T: Send> Send for TypedFixedArena<'_, T> {}
+
+impl<'a, T> TypedFixedArena<'a, T> {
+    /// Make a new typed arena with room for `count` values
+    /// Uses the default system allocator to get the memory
+    /// Panics if the arena can not be created. See `try_with_capacity` for
+    /// the conditions that cause a failure.
+    /// # Arguments
+    /// * `count` - The number of values the arena can hold
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::typed::TypedFixedArena;
+    /// let arena = TypedFixedArena::<u64>::with_capacity(128);
+    /// ```
+    #[cfg(any(feature = "std", test))]
+    pub fn with_capacity(count: usize) -> TypedFixedArena<'a, T> {
+        match TypedFixedArena::try_with_capacity(count) {
+            Ok(arena) => arena,
+            Err(error) => panic!("Failed to create arena: {:?}", error),
+        }
+    }
+
+    /// Make a new typed arena with room for `count` values
+    /// Uses the default system allocator to get the memory
+    /// Returns `AllocError::CapacityOverflow` if `count` values do not fit in
+    /// an `isize`, and the errors from `FixedArena::try_with_capacity`
+    /// otherwise.
+    /// # Arguments
+    /// * `count` - The number of values the arena can hold
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::typed::TypedFixedArena;
+    /// let arena = TypedFixedArena::<u64>::try_with_capacity(128).unwrap();
+    /// assert!(TypedFixedArena::<u64>::try_with_capacity(usize::MAX).is_err());
+    /// ```
+    #[cfg(any(feature = "std", test))]
+    pub fn try_with_capacity(
+        count: usize,
+    ) -> Result<TypedFixedArena<'a, T>, AllocError> {
+        let layout = Layout::array::<T>(count)
+            .map_err(|_| AllocError::CapacityOverflow)?;
+        // Zero sized values take no space, but the arena needs some memory
+        let arena = FixedArena::try_with_capacity(
+            layout.size().max(1),
+            layout.align(),
+        )?;
+        Ok(TypedFixedArena::new(arena))
+    }
+
+    /// Make a new typed arena that stores its values in memory provided by
+    /// the caller. See `FixedArena::from_buffer`.
+    /// # Arguments
+    /// * `buffer` - The memory to allocate from
+    /// # Examples
+    /// ```
+    /// # use core::mem::MaybeUninit;
+    /// # use tea_fixed_arena::typed::TypedFixedArena;
+    /// let mut buffer = [MaybeUninit::uninit(); 256];
+    /// let arena = TypedFixedArena::from_buffer(&mut buffer);
+    /// arena.alloc(5_u32).unwrap();
+    /// assert_eq!(arena.len(), 1);
+    /// ```
+    pub fn from_buffer(
+        buffer: &'a mut [MaybeUninit<u8>],
+    ) -> TypedFixedArena<'a, T> {
+        TypedFixedArena::new(FixedArena::from_buffer(buffer))
+    }
+
+    /// Make a typed arena over an empty arena
+    fn new(arena: FixedArena<'a>) -> TypedFixedArena<'a, T> {
+        TypedFixedArena {
+            arena,
+            start: Cell::new(ptr::NonNull::dangling().as_ptr()),
+            len: Cell::new(0),
+            values: PhantomData,
+        }
+    }
+
+    /// Allocate and initialize a value after the values already in the arena
+    /// # Arguments
+    /// * `val` - The value to initialize the instance to.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::typed::TypedFixedArena;
+    /// let arena = TypedFixedArena::with_capacity(4);
+    /// let value = arena.alloc(String::from("node")).unwrap();
+    /// assert_eq!(value, "node");
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn alloc(&self, val: T) -> Result<&T, AllocError> {
+        let pointer = self.arena.get_alloc_ptr::<T>()? as *mut T;
+        unsafe { ptr::write(pointer, val) };
+        // Only values of type `T` are allocated from the arena, so every value
+        // after the first is right after the previous one
+        if self.len.get() == 0 {
+            self.start.set(pointer);
+        }
+        self.len.set(self.len.get() + 1);
+        unsafe { Ok(&*pointer) }
+    }
+
+    /// The number of values allocated since the last reset
+    pub fn len(&self) -> usize {
+        self.len.get()
+    }
+
+    /// Whether no values have been allocated since the last reset
+    pub fn is_empty(&self) -> bool {
+        self.len.get() == 0
+    }
+
+    /// The values allocated since the last reset, in allocation order
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.start.get(), self.len.get()) }
+    }
+
+    /// The values allocated since the last reset, in allocation order
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.start.get(), self.len.get()) }
+    }
+
+    /// Iterate over the values allocated since the last reset, in allocation
+    /// order
+    pub fn iter(&self) -> slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+
+    /// Iterate mutably over the values allocated since the last reset, in
+    /// allocation order
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, T> {
+        self.as_mut_slice().iter_mut()
+    }
+
+    /// Drop every value in the arena and make its memory available again
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::typed::TypedFixedArena;
+    /// let mut arena = TypedFixedArena::with_capacity(1);
+    /// arena.alloc(1_u32).unwrap();
+    /// arena.reset();
+    /// assert!(arena.is_empty());
+    /// arena.alloc(2_u32).unwrap();
+    /// ```
+    pub fn reset(&mut self) {
+        self.drop_values();
+        self.arena.reset();
+    }
+
+    /// Drop every value in the arena. The length is cleared first, so a
+    /// panicking destructor can't cause a value to be dropped twice.
+    fn drop_values(&mut self) {
+        let values: *mut [T] = self.as_mut_slice();
+        self.len.set(0);
+        unsafe { ptr::drop_in_place(values) };
+    }
+}
+
+impl<T> Drop for TypedFixedArena<'_, T> {
+    fn drop(&mut self) {
+        self.drop_values();
+    }
+}
+
+impl<T> Index<usize> for TypedFixedArena<'_, T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.as_slice()[index]
+    }
+}
+
+impl<T> IndexMut<usize> for TypedFixedArena<'_, T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.as_mut_slice()[index]
+    }
+}
+
+impl<'b, T> IntoIterator for &'b TypedFixedArena<'_, T> {
+    type Item = &'b T;
+    type IntoIter = slice::Iter<'b, T>;
+
+    fn into_iter(self) -> slice::Iter<'b, T> {
+        self.iter()
+    }
+}
+
+impl<'b, T> IntoIterator for &'b mut TypedFixedArena<'_, T> {
+    type Item = &'b mut T;
+    type IntoIter = slice::IterMut<'b, T>;
+
+    fn into_iter(self) -> slice::IterMut<'b, T> {
+        self.iter_mut()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for TypedFixedArena<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_common::{LargerStruct, SmallStruct, ThreeByteStruct};
+    use std::{
+        rc::Rc,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    /// Test that allocated values can be iterated over in order
+    #[test]
+    fn iter() {
+        let arena = TypedFixedArena::with_capacity(16);
+        for x in 0..16 {
+            arena.alloc(LargerStruct { x, y: -x }).unwrap();
+        }
+        assert_eq!(arena.len(), 16);
+        for (index, value) in arena.iter().enumerate() {
+            assert_eq!(
+                *value,
+                LargerStruct {
+                    x: index as i64,
+                    y: -(index as i64)
+                }
+            );
+        }
+    }
+
+    /// Test changing values through iter_mut and indexing
+    #[test]
+    fn iter_mut() {
+        let mut arena = TypedFixedArena::with_capacity(4);
+        for x in 0..4 {
+            arena.alloc(SmallStruct { x, y: 0 }).unwrap();
+        }
+        for value in &mut arena {
+            value.y = value.x * 2;
+        }
+        arena[3].x = 10;
+        assert_eq!(arena[1], SmallStruct { x: 1, y: 2 });
+        assert_eq!(arena[3], SmallStruct { x: 10, y: 6 });
+    }
+
+    /// Test that values with a size that isn't a multiple of the alignment
+    /// of the arena are contiguous
+    #[test]
+    fn odd_size() {
+        let arena = TypedFixedArena::with_capacity(5);
+        for x in 0..5 {
+            arena.alloc(ThreeByteStruct { x, y: x, z: x }).unwrap();
+        }
+        let values = arena.as_slice();
+        assert_eq!(values.len(), 5);
+        assert_eq!(values[4], ThreeByteStruct { x: 4, y: 4, z: 4 });
+    }
+
+    /// Test that references from alloc stay valid while iterating
+    #[test]
+    fn alloc_while_iterating() {
+        let arena = TypedFixedArena::with_capacity(8);
+        let first = arena.alloc(1_u32).unwrap();
+        arena.alloc(2_u32).unwrap();
+        let sum: u32 = arena.iter().sum();
+        arena.alloc(3_u32).unwrap();
+        assert_eq!(*first + sum, 4);
+        assert_eq!(arena.as_slice(), [1, 2, 3]);
+    }
+
+    /// Test that allocating past the capacity fails
+    #[test]
+    fn at_capacity() {
+        let arena = TypedFixedArena::with_capacity(2);
+        arena.alloc(1_u64).unwrap();
+        arena.alloc(2_u64).unwrap();
+        match arena.alloc(3_u64) {
+            Ok(_) => panic!("allocation should fail"),
+            Err(err) => assert!(matches!(err, AllocError::AtCapacity { .. })),
+        };
+        assert_eq!(arena.len(), 2);
+    }
+
+    /// Test that the values are dropped on reset and with the arena
+    #[test]
+    fn drop_values() {
+        let shared = Rc::new(());
+        let mut arena = TypedFixedArena::with_capacity(4);
+        arena.alloc(shared.clone()).unwrap();
+        arena.alloc(shared.clone()).unwrap();
+        assert_eq!(Rc::strong_count(&shared), 3);
+
+        arena.reset();
+        assert_eq!(Rc::strong_count(&shared), 1);
+        assert!(arena.is_empty());
+
+        arena.alloc(shared.clone()).unwrap();
+        drop(arena);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    /// Test an arena of zero sized values
+    #[test]
+    fn zero_sized() {
+        let arena = TypedFixedArena::<()>::with_capacity(0);
+        for _ in 0..100 {
+            arena.alloc(()).unwrap();
+        }
+        assert_eq!(arena.iter().count(), 100);
+    }
+
+    /// Test an arena over a caller provided buffer that may not be aligned
+    /// for the values
+    #[test]
+    fn from_buffer() {
+        let mut buffer = [MaybeUninit::uninit(); 65];
+        let arena = TypedFixedArena::from_buffer(&mut buffer[1..]);
+        while arena.alloc(7_u32).is_ok() {}
+        assert!(arena.len() >= 15);
+        assert!(arena.iter().all(|value| *value == 7));
+    }
+
+    /// Test moving an arena and its values to another thread
+    #[test]
+    fn send() {
+        let shared = Arc::new(Mutex::new(0));
+        let arena = TypedFixedArena::with_capacity(4);
+        arena.alloc(shared.clone()).unwrap();
+        thread::spawn(move || {
+            *arena[0].lock().unwrap() += 1;
+        })
+        .join()
+        .unwrap();
+        assert_eq!(*shared.lock().unwrap(), 1);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+}
@@ -0,0 +1,164 @@
+//! Helpers for initializing memory from `FixedArena::alloc_uninit_slice`
+//! without unsafe code
+
+use core::{mem::MaybeUninit, ptr, slice};
+
+/// Turn a slice of uninitialized elements into a slice of initialized ones
+/// # Arguments
+/// * `values` - the slice to reinterpret
+/// # Safety
+/// Every element of `values` must have been initialized with a valid `T`
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{uninit, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let values = arena.alloc_uninit_slice::<u32>(2).unwrap();
+/// values[0].write(1);
+/// values[1].write(2);
+/// let values = unsafe { uninit::assume_init_slice_mut(values) };
+/// assert_eq!(values, [1, 2]);
+/// ```
+pub unsafe fn assume_init_slice_mut<T>(
+    values: &mut [MaybeUninit<T>],
+) -> &mut [T] {
+    unsafe {
+        slice::from_raw_parts_mut(values.as_mut_ptr() as *mut T, values.len())
+    }
+}
+
+/// Initialize every element of `values` with a copy of the element at the
+/// same index in `src`
+/// Panics if the slices have different lengths
+/// # Arguments
+/// * `values` - the slice to initialize
+/// * `src` - the values to copy
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{uninit, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let values = arena.alloc_uninit_slice::<u32>(3).unwrap();
+/// let values = uninit::write_copy_of_slice(values, &[1, 2, 3]);
+/// assert_eq!(values, [1, 2, 3]);
+/// ```
+pub fn write_copy_of_slice<'s, T>(
+    values: &'s mut [MaybeUninit<T>],
+    src: &[T],
+) -> &'s mut [T]
+where
+    T: Copy,
+{
+    assert_eq!(values.len(), src.len(), "slices have different lengths");
+    unsafe {
+        ptr::copy_nonoverlapping(
+            src.as_ptr(),
+            values.as_mut_ptr() as *mut T,
+            src.len(),
+        );
+        assume_init_slice_mut(values)
+    }
+}
+
+/// Initialize every element of `values` with a clone of the element at the
+/// same index in `src`
+/// Panics if the slices have different lengths
+/// # Arguments
+/// * `values` - the slice to initialize
+/// * `src` - the values to clone
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{uninit, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let names = [String::from("a"), String::from("b")];
+/// let values = arena.alloc_uninit_slice::<String>(2).unwrap();
+/// let values = uninit::write_clone_of_slice(values, &names);
+/// assert_eq!(values, names);
+/// # for value in values.iter_mut() {
+/// #     unsafe { core::ptr::drop_in_place(value) };
+/// # }
+/// ```
+pub fn write_clone_of_slice<'s, T>(
+    values: &'s mut [MaybeUninit<T>],
+    src: &[T],
+) -> &'s mut [T]
+where
+    T: Clone,
+{
+    assert_eq!(values.len(), src.len(), "slices have different lengths");
+    write_with(values, |index| src[index].clone())
+}
+
+/// Initialize every element of `values` with the value `f` returns for its
+/// index. If `f` panics, the elements it already made are leaked.
+/// # Arguments
+/// * `values` - the slice to initialize
+/// * `f` - called with the index of each element, in order, to make its
+///   value
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{uninit, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let values = arena.alloc_uninit_slice::<usize>(3).unwrap();
+/// let values = uninit::write_with(values, |index| index + 1);
+/// assert_eq!(values, [1, 2, 3]);
+/// ```
+pub fn write_with<T, F>(values: &mut [MaybeUninit<T>], mut f: F) -> &mut [T]
+where
+    F: FnMut(usize) -> T,
+{
+    for (index, value) in values.iter_mut().enumerate() {
+        value.write(f(index));
+    }
+    unsafe { assume_init_slice_mut(values) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_common::I32Struct, FixedArena};
+
+    /// Test copying into an uninitialized slice
+    #[test]
+    fn copy() {
+        let arena = FixedArena::with_capacity(1024, 4);
+        let values = arena.alloc_uninit_slice::<I32Struct>(2).unwrap();
+        let src = [I32Struct { x: 1, y: 2 }, I32Struct { x: 3, y: 4 }];
+        let values = write_copy_of_slice(values, &src);
+        assert_eq!(values, src);
+    }
+
+    /// Test that copying between slices of different lengths panics
+    #[test]
+    #[should_panic]
+    fn copy_length_mismatch() {
+        let arena = FixedArena::with_capacity(1024, 4);
+        let values = arena.alloc_uninit_slice::<u32>(2).unwrap();
+        write_copy_of_slice(values, &[1, 2, 3]);
+    }
+
+    /// Test cloning into an uninitialized slice
+    #[test]
+    fn clone() {
+        let arena = FixedArena::with_capacity(1024, 4);
+        let values = arena.alloc_uninit_slice::<Option<u8>>(3).unwrap();
+        let values = write_clone_of_slice(values, &[Some(1), None, Some(3)]);
+        assert_eq!(values, [Some(1), None, Some(3)]);
+    }
+
+    /// Test initializing an uninitialized slice from a closure
+    #[test]
+    fn with() {
+        let arena = FixedArena::with_capacity(1024, 4);
+        let values = arena.alloc_uninit_slice::<bool>(4).unwrap();
+        let values = write_with(values, |index| index % 2 == 0);
+        assert_eq!(values, [true, false, true, false]);
+    }
+
+    /// Test initializing an empty slice
+    #[test]
+    fn with_empty() {
+        let arena = FixedArena::with_capacity(1024, 4);
+        let values = arena.alloc_uninit_slice::<&u8>(0).unwrap();
+        let values = write_with(values, |_| -> &u8 { unreachable!() });
+        assert!(values.is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+use core::{
+    fmt,
+    mem::{self, size_of},
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+};
+
+use crate::{errors::AllocError, FixedArena};
+
+/// A growable vector whose elements are stored in a `FixedArena`. While the
+/// vector is the most recent allocation in the arena it grows in place.
+/// Otherwise growing moves it to the top of the arena, and the old elements
+/// are left behind until the arena is reset.
+/// Running out of space returns an `AllocError` instead of panicking.
+/// Elements are dropped when the vector is dropped, and if the vector is the
+/// most recent allocation its memory is given back to the arena.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{vec::ArenaVec, FixedArena};
+/// let arena = FixedArena::with_capacity(4096, 8);
+/// let mut values = ArenaVec::new_in(&arena);
+/// for index in 0..100_u32 {
+///     values.push(index).unwrap();
+/// }
+/// values.truncate(10);
+/// assert_eq!(values.pop(), Some(9));
+/// assert_eq!(values.iter().sum::<u32>(), 36);
+/// ```
+pub struct ArenaVec<'a, T> {
+    arena: &'a FixedArena<'a>,
+    pointer: NonNull<T>,
+    len: usize,
+    capacity: usize,
+}
+
+impl<'a, T> ArenaVec<'a, T> {
+    /// Make a new, empty vector. Nothing is allocated until the first element
+    /// is pushed.
+    /// # Arguments
+    /// * `arena` - The arena to store the elements in
+    pub fn new_in(arena: &'a FixedArena<'a>) -> ArenaVec<'a, T> {
+        ArenaVec {
+            arena,
+            pointer: NonNull::dangling(),
+            len: 0,
+            capacity: if size_of::<T>() == 0 { usize::MAX } else { 0 },
+        }
+    }
+
+    /// Make a new, empty vector with space for `capacity` elements
+    /// # Arguments
+    /// * `capacity` - The number of elements to make space for
+    /// * `arena` - The arena to store the elements in
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{vec::ArenaVec, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let values = ArenaVec::<u64>::with_capacity_in(16, &arena).unwrap();
+    /// assert_eq!(values.capacity(), 16);
+    /// assert!(ArenaVec::<u64>::with_capacity_in(1024, &arena).is_err());
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn with_capacity_in(
+        capacity: usize,
+        arena: &'a FixedArena<'a>,
+    ) -> Result<ArenaVec<'a, T>, AllocError> {
+        let mut result = ArenaVec::new_in(arena);
+        result.reserve_exact(capacity)?;
+        Ok(result)
+    }
+
+    /// The arena the elements are stored in
+    #[cfg(feature = "track-callers")]
+    pub(crate) fn arena(&self) -> &'a FixedArena<'a> {
+        self.arena
+    }
+
+    /// The number of elements in the vector
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the vector has no elements
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of elements the vector can hold without growing
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Make sure there is space for at least `additional` more elements.
+    /// Space for more elements may be reserved to avoid growing often.
+    /// # Arguments
+    /// * `additional` - The number of elements to make space for
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn reserve(&mut self, additional: usize) -> Result<(), AllocError> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(AllocError::LayoutOverflow)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+        let amortized = required.max(self.capacity * 2).max(4);
+        match self.grow_to(amortized) {
+            Ok(()) => Ok(()),
+            Err(_) => self.grow_to(required),
+        }
+    }
+
+    /// Make sure there is space for exactly `additional` more elements
+    /// # Arguments
+    /// * `additional` - The number of elements to make space for
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn reserve_exact(
+        &mut self,
+        additional: usize,
+    ) -> Result<(), AllocError> {
+        let required = self
+            .len
+            .checked_add(additional)
+            .ok_or(AllocError::LayoutOverflow)?;
+        if required <= self.capacity {
+            return Ok(());
+        }
+        self.grow_to(required)
+    }
+
+    /// Grow the storage to `capacity` elements, in place if the vector is the
+    /// most recent allocation, and by moving it otherwise
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    fn grow_to(&mut self, capacity: usize) -> Result<(), AllocError> {
+        let layout = FixedArena::array_layout::<T>(capacity)?;
+        if self.capacity > 0
+            && self
+                .arena
+                .resize_last(
+                    self.pointer.as_ptr() as *const u8,
+                    self.capacity * size_of::<T>(),
+                    layout.size(),
+                    layout.align(),
+                )
+                .is_ok()
+        {
+            self.capacity = capacity;
+            return Ok(());
+        }
+
+        let pointer =
+            self.arena.get_alloc_ptr_with_layout::<[T]>(layout)? as *mut T;
+        unsafe {
+            ptr::copy_nonoverlapping(self.pointer.as_ptr(), pointer, self.len);
+            self.pointer = NonNull::new_unchecked(pointer);
+        }
+        self.capacity = capacity;
+        Ok(())
+    }
+
+    /// Add an element to the end of the vector
+    /// # Arguments
+    /// * `val` - The element to add
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn push(&mut self, val: T) -> Result<(), AllocError> {
+        if self.len == self.capacity {
+            self.reserve(1)?;
+        }
+        unsafe {
+            ptr::write(self.pointer.as_ptr().add(self.len), val);
+        }
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Add every element of an iterator to the end of the vector. Stops at the
+    /// first element that doesn't fit, keeping the elements added before it.
+    /// # Arguments
+    /// * `iter` - The elements to add
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{vec::ArenaVec, FixedArena};
+    /// let arena = FixedArena::with_capacity(64, 8);
+    /// let mut values = ArenaVec::new_in(&arena);
+    /// values.extend(0..8_u32).unwrap();
+    /// assert!(values.extend(0..100).is_err());
+    /// assert_eq!(values.len(), 16);
+    /// ```
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn extend<I>(&mut self, iter: I) -> Result<(), AllocError>
+    where
+        I: IntoIterator<Item = T>,
+    {
+        let iter = iter.into_iter();
+        let _ = self.reserve(iter.size_hint().0);
+        for val in iter {
+            self.push(val)?;
+        }
+        Ok(())
+    }
+
+    /// Add clones of every element of a slice to the end of the vector
+    /// # Arguments
+    /// * `values` - The elements to add
+    #[cfg_attr(feature = "track-callers", track_caller)]
+    pub fn extend_from_slice(&mut self, values: &[T]) -> Result<(), AllocError>
+    where
+        T: Clone,
+    {
+        self.reserve(values.len())?;
+        for val in values {
+            unsafe {
+                ptr::write(self.pointer.as_ptr().add(self.len), val.clone());
+            }
+            self.len += 1;
+        }
+        Ok(())
+    }
+
+    /// Remove the last element of the vector and return it
+    pub fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        unsafe { Some(ptr::read(self.pointer.as_ptr().add(self.len))) }
+    }
+
+    /// Drop the elements after the first `len`. Does nothing if the vector
+    /// has `len` or fewer elements. The capacity is unchanged.
+    /// # Arguments
+    /// * `len` - The number of elements to keep
+    pub fn truncate(&mut self, len: usize) {
+        if len >= self.len {
+            return;
+        }
+        let tail = ptr::slice_from_raw_parts_mut(
+            unsafe { self.pointer.as_ptr().add(len) },
+            self.len - len,
+        );
+        self.len = len;
+        unsafe {
+            ptr::drop_in_place(tail);
+        }
+    }
+
+    /// Drop every element of the vector
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// The elements of the vector as a slice
+    pub fn as_slice(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.pointer.as_ptr(), self.len) }
+    }
+
+    /// The elements of the vector as a mutable slice
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        unsafe { slice::from_raw_parts_mut(self.pointer.as_ptr(), self.len) }
+    }
+
+    /// Turn the vector into a slice that lives as long as the arena borrow.
+    /// The elements are not dropped, like other values allocated with
+    /// `FixedArena::alloc`.
+    /// # Examples
+    /// ```
+    /// # use tea_fixed_arena::{vec::ArenaVec, FixedArena};
+    /// let arena = FixedArena::with_capacity(4096, 8);
+    /// let mut values = ArenaVec::new_in(&arena);
+    /// values.extend(0..4_u32).unwrap();
+    /// let values: &mut [u32] = values.into_slice();
+    /// assert_eq!(values, [0, 1, 2, 3]);
+    /// ```
+    pub fn into_slice(self) -> &'a mut [T] {
+        let result = unsafe {
+            slice::from_raw_parts_mut(self.pointer.as_ptr(), self.len)
+        };
+        mem::forget(self);
+        result
+    }
+}
+
+impl<T> Deref for ArenaVec<'_, T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T> DerefMut for ArenaVec<'_, T> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArenaVec<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_slice(), f)
+    }
+}
+
+impl<T> Drop for ArenaVec<'_, T> {
+    /// Drops the elements, and gives the memory back to the arena if the
+    /// vector is the most recent allocation
+    fn drop(&mut self) {
+        self.clear();
+        if self.capacity > 0 && size_of::<T>() > 0 {
+            let _ = self.arena.resize_last(
+                self.pointer.as_ptr() as *const u8,
+                self.capacity * size_of::<T>(),
+                0,
+                align_of::<T>(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{string::String, sync::Arc};
+
+    use crate::test_common::{I32Struct, SmallStruct};
+
+    const DEFAULT_ALIGN: usize = 8;
+
+    /// Test pushing and reading back elements
+    #[test]
+    fn push() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        for index in 0..64 {
+            values
+                .push(I32Struct {
+                    x: index,
+                    y: -index,
+                })
+                .unwrap();
+        }
+        assert_eq!(values.len(), 64);
+        for (index, value) in values.iter().enumerate() {
+            assert_eq!(value.x, index as i32);
+            assert_eq!(value.y, -(index as i32));
+        }
+    }
+
+    /// Test that the most recent allocation grows in place
+    #[test]
+    fn grows_in_place() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        values.push(0_u32).unwrap();
+        let start = values.as_ptr();
+        values.extend(1..100).unwrap();
+        assert_eq!(values.as_ptr(), start);
+        assert_eq!(arena.used.get(), values.capacity() * 4);
+    }
+
+    /// Test that a vector that isn't the most recent allocation is moved
+    #[test]
+    fn relocates() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values = ArenaVec::with_capacity_in(4, &arena).unwrap();
+        values.extend(0..4_u32).unwrap();
+        let start = values.as_ptr();
+        let other = arena.alloc(SmallStruct { x: 1, y: 2 }).unwrap();
+
+        values.push(4).unwrap();
+        assert_ne!(values.as_ptr(), start);
+        assert_eq!(*values, [0, 1, 2, 3, 4]);
+        assert_eq!(*other, SmallStruct { x: 1, y: 2 });
+    }
+
+    /// Test that running out of space returns an error and keeps the
+    /// elements
+    #[test]
+    fn at_capacity() {
+        let arena = FixedArena::with_capacity(64, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        for index in 0..16_u32 {
+            values.push(index).unwrap();
+        }
+        match values.push(16) {
+            Ok(_) => panic!("push should fail"),
+            Err(err) => assert!(matches!(err, AllocError::AtCapacity { .. })),
+        };
+        assert_eq!(values.len(), 16);
+        assert_eq!(values[15], 15);
+    }
+
+    /// Test that growth falls back to the exact size when doubling doesn't
+    /// fit
+    #[test]
+    fn exact_growth_near_capacity() {
+        let arena = FixedArena::with_capacity(40, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        values.extend(0..8_u32).unwrap();
+        values.push(8).unwrap();
+        values.push(9).unwrap();
+        assert_eq!(values.len(), 10);
+        assert!(values.push(10).is_err());
+    }
+
+    /// Test popping, truncating and clearing
+    #[test]
+    fn pop_truncate_clear() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        assert_eq!(values.pop(), None);
+        values.extend_from_slice(&[1_u8, 2, 3, 4, 5]).unwrap();
+        assert_eq!(values.pop(), Some(5));
+        values.truncate(2);
+        assert_eq!(*values, [1, 2]);
+        values.truncate(10);
+        assert_eq!(values.len(), 2);
+        values.clear();
+        assert!(values.is_empty());
+    }
+
+    /// Test that elements are dropped by truncate and by dropping the vector
+    #[test]
+    fn drops_elements() {
+        let shared = Arc::new(());
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        {
+            let mut values = ArenaVec::new_in(&arena);
+            for _ in 0..8 {
+                values.push(shared.clone()).unwrap();
+            }
+            values.truncate(4);
+            assert_eq!(Arc::strong_count(&shared), 5);
+        }
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    /// Test that dropping the most recent vector gives its memory back
+    #[test]
+    fn drop_gives_back_memory() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        arena.alloc(1_u64).unwrap();
+        {
+            let mut values = ArenaVec::new_in(&arena);
+            values.push(String::from("dropped")).unwrap();
+        }
+        assert_eq!(arena.used.get(), 8);
+    }
+
+    /// Test vectors of zero sized types
+    #[test]
+    fn zero_sized() {
+        let arena = FixedArena::with_capacity(8, DEFAULT_ALIGN);
+        let mut values = ArenaVec::new_in(&arena);
+        for _ in 0..1000 {
+            values.push(()).unwrap();
+        }
+        assert_eq!(values.len(), 1000);
+        assert_eq!(arena.used.get(), 0);
+    }
+
+    /// Test turning a vector into a slice that outlives it
+    #[test]
+    fn into_slice() {
+        let arena = FixedArena::with_capacity(1024, DEFAULT_ALIGN);
+        let slice = {
+            let mut values = ArenaVec::new_in(&arena);
+            values.extend(0..4_u32).unwrap();
+            values.into_slice()
+        };
+        slice[0] = 10;
+        assert_eq!(slice, [10, 1, 2, 3]);
+        assert_eq!(arena.used.get(), 16);
+    }
+}
@@ -0,0 +1,214 @@
+//! Types that are valid when all of their bytes are 0, which can be
+//! allocated with `FixedArena::alloc_zeroed` and `alloc_zeroed_array`
+
+use core::{
+    cell::{Cell, UnsafeCell},
+    marker::{PhantomData, PhantomPinned},
+    mem::{ManuallyDrop, MaybeUninit},
+    num::{
+        NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8,
+        NonZeroIsize, NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64,
+        NonZeroU8, NonZeroUsize, Wrapping,
+    },
+    ptr::NonNull,
+    sync::atomic::{
+        AtomicBool, AtomicI16, AtomicI32, AtomicI64, AtomicI8, AtomicIsize,
+        AtomicPtr, AtomicU16, AtomicU32, AtomicU64, AtomicU8, AtomicUsize,
+    },
+};
+
+/// Derive `Zeroable` for a struct whose fields are all `Zeroable`. Needs the
+/// `derive` feature.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{zeroable::Zeroable, FixedArena};
+/// #[derive(Zeroable)]
+/// struct Point {
+///     x: f32,
+///     y: f32,
+/// }
+///
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let point = arena.alloc_zeroed::<Point>().unwrap();
+/// assert_eq!(point.x, 0.0);
+/// ```
+/// A struct with a field that can't be all zeros does not compile
+/// ```compile_fail
+/// # use tea_fixed_arena::zeroable::Zeroable;
+/// #[derive(Zeroable)]
+/// struct Name<'a> {
+///     name: &'a str,
+/// }
+/// ```
+#[cfg(feature = "derive")]
+pub use tea_fixed_arena_derive::Zeroable;
+
+/// A type for which a value with all of its bytes set to 0 is valid
+/// # Safety
+/// Implementing this for a type where all zeros is not a valid value, such
+/// as a reference, `NonZeroU32` or an enum without a variant with
+/// discriminant 0, lets safe code create an invalid value. Prefer
+/// `#[derive(Zeroable)]` for structs, which checks every field.
+/// # Examples
+/// ```
+/// # use tea_fixed_arena::{zeroable::Zeroable, FixedArena};
+/// #[repr(C)]
+/// struct Color {
+///     red: u8,
+///     green: u8,
+///     blue: u8,
+/// }
+///
+/// // Safety: every field of Color is an integer
+/// unsafe impl Zeroable for Color {}
+///
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let color = arena.alloc_zeroed::<Color>().unwrap();
+/// assert_eq!(color.red, 0);
+/// ```
+/// Types where all zeros is not valid can not be allocated zeroed
+/// ```compile_fail
+/// # use tea_fixed_arena::FixedArena;
+/// let arena = FixedArena::with_capacity(4096, 4);
+/// let reference = arena.alloc_zeroed::<&u8>().unwrap();
+/// ```
+pub unsafe trait Zeroable: Sized {}
+
+/// Implement `Zeroable` for types that have no type parameters
+macro_rules! impl_zeroable {
+    ($($type:ty),* $(,)?) => {
+        $(unsafe impl Zeroable for $type {})*
+    };
+}
+
+impl_zeroable!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    PhantomPinned,
+    AtomicBool,
+    AtomicU8,
+    AtomicU16,
+    AtomicU32,
+    AtomicU64,
+    AtomicUsize,
+    AtomicI8,
+    AtomicI16,
+    AtomicI32,
+    AtomicI64,
+    AtomicIsize,
+    // The all zeros value of these is `None`
+    Option<NonZeroU8>,
+    Option<NonZeroU16>,
+    Option<NonZeroU32>,
+    Option<NonZeroU64>,
+    Option<NonZeroU128>,
+    Option<NonZeroUsize>,
+    Option<NonZeroI8>,
+    Option<NonZeroI16>,
+    Option<NonZeroI32>,
+    Option<NonZeroI64>,
+    Option<NonZeroI128>,
+    Option<NonZeroIsize>,
+);
+
+unsafe impl<T: ?Sized> Zeroable for PhantomData<T> {}
+unsafe impl<T> Zeroable for MaybeUninit<T> {}
+unsafe impl<T: Zeroable> Zeroable for ManuallyDrop<T> {}
+unsafe impl<T: Zeroable> Zeroable for Wrapping<T> {}
+unsafe impl<T: Zeroable> Zeroable for Cell<T> {}
+unsafe impl<T: Zeroable> Zeroable for UnsafeCell<T> {}
+unsafe impl<T: Zeroable, const N: usize> Zeroable for [T; N] {}
+
+// Null pointers
+unsafe impl<T> Zeroable for *const T {}
+unsafe impl<T> Zeroable for *mut T {}
+unsafe impl<T> Zeroable for AtomicPtr<T> {}
+
+// The all zeros value of these is `None`
+unsafe impl<T> Zeroable for Option<&T> {}
+unsafe impl<T> Zeroable for Option<&mut T> {}
+unsafe impl<T> Zeroable for Option<NonNull<T>> {}
+
+/// Implement `Zeroable` for tuples of `Zeroable` types
+macro_rules! impl_zeroable_tuple {
+    ($($name:ident),+) => {
+        unsafe impl<$($name: Zeroable),+> Zeroable for ($($name,)+) {}
+    };
+}
+
+impl_zeroable_tuple!(A);
+impl_zeroable_tuple!(A, B);
+impl_zeroable_tuple!(A, B, C);
+impl_zeroable_tuple!(A, B, C, D);
+impl_zeroable_tuple!(A, B, C, D, E);
+impl_zeroable_tuple!(A, B, C, D, E, F);
+impl_zeroable_tuple!(A, B, C, D, E, F, G);
+impl_zeroable_tuple!(A, B, C, D, E, F, G, H);
+impl_zeroable_tuple!(A, B, C, D, E, F, G, H, I);
+impl_zeroable_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_zeroable_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_zeroable_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_common::{I32Struct, MixedStruct},
+        FixedArena,
+    };
+    use tea_fixed_arena_derive::Zeroable;
+
+    #[derive(Zeroable)]
+    struct Wrapper<T>(T, [u16; 3]);
+
+    #[derive(Zeroable)]
+    struct Unit;
+
+    /// Test zeroed allocation of primitives, tuples and arrays
+    #[test]
+    fn builtin_types() {
+        let arena = FixedArena::with_capacity(1024, 8);
+        assert!(!*arena.alloc_zeroed::<bool>().unwrap());
+        assert_eq!(*arena.alloc_zeroed::<char>().unwrap(), '\0');
+        assert_eq!(
+            *arena.alloc_zeroed::<(u8, i64, f32)>().unwrap(),
+            (0, 0, 0.0)
+        );
+        assert_eq!(*arena.alloc_zeroed::<[u32; 4]>().unwrap(), [0; 4]);
+        assert_eq!(*arena.alloc_zeroed::<Option<NonZeroU32>>().unwrap(), None);
+        assert!(arena.alloc_zeroed::<Option<&u8>>().unwrap().is_none());
+        assert!(arena.alloc_zeroed::<*const u8>().unwrap().is_null());
+    }
+
+    /// Test zeroed allocation of derived structs
+    #[test]
+    fn derived() {
+        let arena = FixedArena::with_capacity(1024, 8);
+        let value = arena.alloc_zeroed::<MixedStruct>().unwrap();
+        assert_eq!(*value, MixedStruct::default());
+
+        let values = arena.alloc_zeroed_array::<I32Struct>(4).unwrap();
+        assert_eq!(values, [I32Struct::default(); 4]);
+
+        let value = arena.alloc_zeroed::<Wrapper<Option<&u8>>>().unwrap();
+        assert!(value.0.is_none());
+        assert_eq!(value.1, [0; 3]);
+
+        arena.alloc_zeroed::<Unit>().unwrap();
+    }
+}